@@ -0,0 +1,119 @@
+use euler::{vec3, Vec3};
+
+use std::str::FromStr;
+
+use crate::color::Color;
+
+// Arbitrary output variables, passes of what camera rays hit first next to the beauty image.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Aov {
+    // base color of the material
+    Albedo,
+    // shading normal in world space, facing the camera
+    Normal,
+    // distance along the camera ray, 0 where it misses
+    Depth,
+    // index of the object plus one, 0 where the ray misses
+    Object,
+    // light the surface emits itself
+    Emission,
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "albedo" => Ok(Self::Albedo),
+            "normal" => Ok(Self::Normal),
+            "depth" => Ok(Self::Depth),
+            "object" => Ok(Self::Object),
+            "emission" => Ok(Self::Emission),
+            _ => Err(format!("unknown output variable: {}", name)),
+        }
+    }
+}
+
+impl Aov {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Albedo => "albedo",
+            Self::Normal => "normal",
+            Self::Depth => "depth",
+            Self::Object => "object",
+            Self::Emission => "emission",
+        }
+    }
+    // Channel names and the components of the buffer's colors they hold.
+    pub fn channels(self) -> &'static [(&'static str, usize)] {
+        match self {
+            Self::Albedo | Self::Emission => &[("R", 0), ("G", 1), ("B", 2)],
+            Self::Normal => &[("X", 0), ("Y", 1), ("Z", 2)],
+            Self::Depth => &[("Z", 0)],
+            Self::Object => &[("id", 0)],
+        }
+    }
+    // Whether samples are averaged; object indices can't be, the first sample's one stays.
+    pub fn accumulates(self) -> bool {
+        self != Self::Object
+    }
+    // An 8-bit picture of the pass to look at: colors as sRGB, normals mapped from [-1, 1],
+    // depth from black up close to white at the farthest hit and objects in random colors.
+    pub fn to_rgba8(self, buffer: &[Color]) -> Vec<u8> {
+        let far = buffer.iter().fold(0f32, |far, pixel| far.max(pixel.0[0]));
+        buffer
+            .iter()
+            .flat_map(|pixel| {
+                let [x, y, z, _] = pixel.0;
+                match self {
+                    Self::Albedo | Self::Emission => Color([x, y, z, 1.0]).into_u8(),
+                    Self::Normal => [x, y, z, 1.0].map(|c| ((c * 0.5 + 0.5) * 255.0) as u8),
+                    Self::Depth if far > 0.0 => {
+                        let d = ((x / far) * 255.0) as u8;
+                        [d, d, d, 255]
+                    }
+                    Self::Depth => [0, 0, 0, 255],
+                    Self::Object => match x as u32 {
+                        0 => [0, 0, 0, 255],
+                        id => {
+                            let [r, g, b, _] = id.wrapping_mul(0x9e37_79b9).to_le_bytes();
+                            [r, g, b, 255]
+                        }
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+// First-hit values of one camera ray, all zero when it misses.
+#[derive(Clone, Copy)]
+pub struct Features {
+    pub albedo: Color,
+    pub normal: Vec3,
+    pub depth: f32,
+    pub object: Option<usize>,
+    pub emission: Color,
+}
+
+impl Features {
+    pub fn new() -> Self {
+        Self {
+            albedo: Color::BLACK,
+            normal: vec3![],
+            depth: 0.0,
+            object: None,
+            emission: Color::BLACK,
+        }
+    }
+    pub fn get(&self, aov: Aov) -> Color {
+        let [r, g, b, _] = match aov {
+            Aov::Albedo => self.albedo.0,
+            Aov::Normal => [self.normal.x, self.normal.y, self.normal.z, 0.0],
+            Aov::Depth => [self.depth; 4],
+            Aov::Object => [self.object.map_or(0.0, |i| (i + 1) as f32); 4],
+            Aov::Emission => self.emission.0,
+        };
+        Color([r, g, b, 1.0])
+    }
+}
@@ -0,0 +1,272 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::time::Instant;
+
+use euler::{vec3, Vec3};
+use rand::prelude::*;
+
+use crate::bvh::{Bvh, Objects};
+use crate::color::Color;
+use crate::primitives::*;
+use crate::rng::Pcg;
+use crate::scene::Scene;
+use crate::sky::Constant;
+use crate::tracer::Tracer;
+use crate::utils::*;
+
+pub const USAGE: &str = "usage: ray_tracing bench [--rings N] [--rays N]
+    --rings N  latitude rings of the benchmark sphere mesh, 4 * N^2 triangles (default 64)
+    --rays N   rays cast per method (default 2000)";
+
+pub const FURNACE_USAGE: &str = "usage: ray_tracing furnace [--albedo A] [--spp N]
+    --albedo A  diffuse albedo of the sphere (default 0.5)
+    --spp N     samples per pixel (default 64)";
+
+// Tessellated sphere, `rings` latitude bands by `2 * rings` longitude segments.
+fn sphere_mesh(
+    center: Vec3,
+    rad: f32,
+    rings: usize,
+    mat: Material,
+) -> Vec<Box<dyn Object3d + Sync>> {
+    let segments = rings * 2;
+    let point = |ring: usize, seg: usize| {
+        let theta = PI * ring as f32 / rings as f32;
+        let phi = 2.0 * PI * seg as f32 / segments as f32;
+        center
+            + rad
+                * vec3![
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos()
+                ]
+    };
+    let mut trigs: Vec<Box<dyn Object3d + Sync>> = vec![];
+    for ring in 0..rings {
+        for seg in 0..segments {
+            let (a, b) = (point(ring, seg), point(ring, seg + 1));
+            let (c, d) = (point(ring + 1, seg), point(ring + 1, seg + 1));
+            trigs.push(Box::new(Trig::from(a, c, b, mat)));
+            trigs.push(Box::new(Trig::from(b, c, d, mat)));
+        }
+    }
+    trigs
+}
+
+// The tracer's closest hit before the BVH: test every object.
+fn linear_closest(objects: &Objects, ray: &Ray) -> Option<f32> {
+    objects.iter().fold(None, |best, obj| {
+        obj.hit(ray, 0.0, best.unwrap_or(f32::INFINITY))
+            .map(|hit| hit.t)
+            .or(best)
+    })
+}
+
+// Parses `--name value` pairs into `defaults`, in order.
+fn parse(args: &[String], names: &[&str], defaults: &mut [f32], usage: &str) -> Result<(), String> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let index = names
+            .iter()
+            .position(|name| name == arg)
+            .ok_or_else(|| format!("unknown argument: {}\n{}", arg, usage))?;
+        defaults[index] = args
+            .next()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("missing or invalid value for {}\n{}", arg, usage))?;
+    }
+    Ok(())
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut values = [64.0, 2000.0];
+    parse(args, &["--rings", "--rays"], &mut values, USAGE)?;
+    let (rings, ray_count) = (values[0] as usize, values[1] as usize);
+
+    let mat = Material::from(Color::WHITE, 0.0, 0.0, Color::BLACK);
+    let objects = sphere_mesh(vec3![], 2.0, rings, mat);
+    let camera = Camera::look_at(vec3![4.0, 4.0, 4.0], vec3![], vec3![0.0, 0.0, 1.0]);
+    let mut rng = Pcg::from(0, 0, 0);
+    let rays: Vec<Ray> = (0..ray_count)
+        .map(|_| {
+            let (u, v) = (rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5));
+            camera.get_ray(u, v, &mut rng)
+        })
+        .collect();
+    println!("{} triangles, {} rays", objects.len(), rays.len());
+
+    let start = Instant::now();
+    let bvh = Bvh::from(&objects);
+    let build_time = start.elapsed().as_secs_f32();
+
+    let start = Instant::now();
+    let linear: Vec<Option<f32>> = rays
+        .iter()
+        .map(|ray| linear_closest(&objects, ray))
+        .collect();
+    let linear_time = start.elapsed().as_secs_f32();
+
+    let start = Instant::now();
+    let accelerated: Vec<Option<f32>> = rays
+        .iter()
+        .map(|ray| {
+            bvh.closest(&objects, ray, 0.0, f32::INFINITY)
+                .map(|hit| hit.t)
+        })
+        .collect();
+    let bvh_time = start.elapsed().as_secs_f32();
+
+    let mismatches = linear
+        .iter()
+        .zip(&accelerated)
+        .filter(|(a, b)| match (a, b) {
+            (Some(a), Some(b)) => (a - b).abs() > 1e-4,
+            (a, b) => a.is_some() != b.is_some(),
+        })
+        .count();
+
+    println!("bvh build: {:.2} ms", build_time * 1000.0);
+    println!("linear:    {:.2} ms", linear_time * 1000.0);
+    println!("bvh:       {:.2} ms", bvh_time * 1000.0);
+    println!("speedup:   {:.1}x", linear_time / bvh_time);
+    println!("mismatching hits: {}", mismatches);
+    Ok(())
+}
+
+// White furnace: a sphere of `mat` filling the whole view under a uniform white sky, and
+// the mean of the image after `spp` samples per pixel. Light only leaves the sphere's surface
+// towards the camera after bouncing off or through it, so an energy conserving BSDF/PDF pair
+// can't make it brighter than white, and a diffuse one converges to exactly its albedo.
+fn furnace_mean(mat: Material, size: usize, spp: usize) -> f32 {
+    let source = move |_t: f32| {
+        let objects: Vec<Box<dyn Object3d + Sync>> =
+            vec![Box::new(Sphere::from(vec3![], 2.0, mat))];
+        Scene {
+            camera: Camera::look_at(vec3![2.2, 0.0, 0.0], vec3![], vec3![0.0, 0.0, 1.0]),
+            objects,
+            lights: vec![],
+            sky: Arc::new(Constant::from(Color::WHITE)),
+        }
+    };
+    let mut tracer = Tracer::with_source(size, size, Box::new(source));
+    for _ in 0..spp {
+        tracer.accumulate(0.0);
+    }
+
+    let screen = tracer.screen();
+    screen
+        .iter()
+        .map(|pix| (pix.0[0] + pix.0[1] + pix.0[2]) / 3.0)
+        .sum::<f32>()
+        / screen.len() as f32
+}
+
+pub fn furnace(args: &[String]) -> Result<(), String> {
+    let mut values = [0.5, 64.0];
+    parse(args, &["--albedo", "--spp"], &mut values, FURNACE_USAGE)?;
+    let (albedo, spp) = (values[0], values[1] as usize);
+    // the error is relative to the albedo, and without samples there is no mean
+    if albedo <= 0.0 {
+        return Err(format!("albedo must be above zero\n{}", FURNACE_USAGE));
+    }
+    if spp == 0 {
+        return Err(format!(
+            "samples per pixel must not be zero\n{}",
+            FURNACE_USAGE
+        ));
+    }
+
+    let mat = Material::from(Color([albedo, albedo, albedo, 1.0]), 0.0, 0.0, Color::BLACK);
+    let mean = furnace_mean(mat, 64, spp);
+    let error = (mean - albedo).abs() / albedo;
+    println!("albedo:    {:.4}", albedo);
+    println!("mean:      {:.4}", mean);
+    println!("error:     {:.2}%", error * 100.0);
+    if error.is_nan() || error > 0.01 {
+        return Err("white furnace test failed: energy is not conserved".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: usize = 24;
+    const SPP: usize = 32;
+
+    fn gray(albedo: f32) -> Color {
+        Color([albedo, albedo, albedo, 1.0])
+    }
+
+    #[test]
+    fn furnace_default_material() {
+        for albedo in [0.5, 1.0] {
+            let mat = Material::from(gray(albedo), 0.0, 0.0, Color::BLACK);
+            let mean = furnace_mean(mat, SIZE, SPP);
+            assert!(
+                (mean - albedo).abs() / albedo < 0.01,
+                "{} for {}",
+                mean,
+                albedo
+            );
+        }
+    }
+
+    // A mirror reflects everything. Single scattering GGX loses the light bounced off more than
+    // one microfacet, more the rougher it gets, but must never gain any.
+    #[test]
+    fn furnace_rough_metal() {
+        let mut last = f32::INFINITY;
+        for roughness in [0.0, 0.3, 0.6, 1.0] {
+            let mat =
+                Material::from(Color::WHITE, 1.0, 0.0, Color::BLACK).with_roughness(roughness);
+            let mean = furnace_mean(mat, SIZE, SPP);
+            if roughness == 0.0 {
+                assert!((mean - 1.0).abs() < 0.01, "{} for a mirror", mean);
+            }
+            assert!(
+                mean < 1.01 && mean < last + 0.01,
+                "{} at roughness {}",
+                mean,
+                roughness
+            );
+            assert!(mean > 0.25, "{} at roughness {}", mean, roughness);
+            last = mean;
+        }
+    }
+
+    #[test]
+    fn furnace_rough_plastic() {
+        for roughness in [0.0, 0.3, 0.6] {
+            let mat = Material::from(Color::WHITE, 0.0, 0.0, Color::BLACK)
+                .with_roughness(roughness)
+                .with_specular(0.5)
+                .with_clearcoat(1.0, 0.1);
+            let mean = furnace_mean(mat, SIZE, SPP);
+            assert!(
+                (0.9..1.01).contains(&mean),
+                "{} at roughness {}",
+                mean,
+                roughness
+            );
+        }
+    }
+
+    // Clear glass only ever reflects or refracts, every path leaves the sphere unchanged.
+    #[test]
+    fn furnace_rough_glass() {
+        for roughness in [0.0, 0.3] {
+            let mat = Material::from(Color::WHITE, 0.0, 0.0, Color::BLACK)
+                .with_roughness(roughness)
+                .with_glass(1.0, 1.5);
+            let mean = furnace_mean(mat, SIZE, SPP);
+            assert!(
+                (mean - 1.0).abs() < 0.01,
+                "{} at roughness {}",
+                mean,
+                roughness
+            );
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use euler::{vec3, Vec3};
+use rand::prelude::*;
+
+use std::f32::consts::PI;
+
+use crate::color::Color;
+use crate::rng::Pcg;
+use crate::utils::{Frame, Material};
+
+// Principled surface model: a Lambertian base, a GGX specular lobe tinted towards the base
+// color by `metallicity`, a weaker untinted GGX clearcoat on top and smooth-ish glass
+// transmission. Directions point away from the surface, `norm` faces the viewer.
+
+pub struct BsdfSample {
+    pub dir: Vec3,
+    // f * cos(theta) / pdf, what the light arriving from `dir` gets multiplied with
+    pub weight: Color,
+    pub pdf: f32,
+    // sampled from a lobe `eval` can't reproduce (transmission), light sampling must not count it
+    pub delta: bool,
+}
+
+fn rgb(color: Color) -> Vec3 {
+    vec3![color.0[0], color.0[1], color.0[2]]
+}
+
+fn to_color(v: Vec3) -> Color {
+    Color([v.x, v.y, v.z, 1.0])
+}
+
+fn reflect(v: Vec3, n: Vec3) -> Vec3 {
+    2.0 * v.dot(n) * n - v
+}
+
+fn alpha(roughness: f32) -> f32 {
+    (roughness * roughness).max(1e-3)
+}
+
+// GGX normal distribution, `cos` is n . m.
+fn ggx_d(cos: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = cos * cos * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+// Smith masking for one direction, `cos` is n . w.
+fn ggx_g1(cos: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    2.0 * cos / (cos + (a2 + (1.0 - a2) * cos * cos).sqrt())
+}
+
+// Visible normal sampling (Heitz 2018), `wo` in the local frame.
+fn sample_ggx_vndf(wo: Vec3, alpha: f32, u1: f32, u2: f32) -> Vec3 {
+    let vh = vec3![alpha * wo.x, alpha * wo.y, wo.z].normalize();
+    let len2 = vh.x * vh.x + vh.y * vh.y;
+    let t1 = if len2 > 0.0 {
+        vec3![-vh.y, vh.x, 0.0] / len2.sqrt()
+    } else {
+        vec3![1.0, 0.0, 0.0]
+    };
+    let t2 = vh.cross(t1);
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + vh.z);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+    let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+    vec3![alpha * nh.x, alpha * nh.y, nh.z.max(0.0)].normalize()
+}
+
+fn schlick(f0: Vec3, cos: f32) -> Vec3 {
+    let s = (1.0 - cos).clamp(0.0, 1.0).powi(5);
+    f0 + s * (vec3![1.0, 1.0, 1.0] - f0)
+}
+
+struct Lobes {
+    diffuse: f32,
+    specular: f32,
+    clearcoat: f32,
+}
+
+impl Lobes {
+    // Selection probabilities of the reflective lobes seen from `wo`.
+    fn from(mat: &Material, wo: Vec3) -> Self {
+        let diffuse = diffuse_weight(mat, wo.z);
+        let specular = if has_specular(mat) { 1.0 } else { 0.0 };
+        let clearcoat = 0.25 * mat.clearcoat;
+        let total = diffuse + specular + clearcoat;
+        if total <= 0.0 {
+            return Self {
+                diffuse: 1.0,
+                specular: 0.0,
+                clearcoat: 0.0,
+            };
+        }
+        Self {
+            diffuse: diffuse / total,
+            specular: specular / total,
+            clearcoat: clearcoat / total,
+        }
+    }
+}
+
+fn has_specular(mat: &Material) -> bool {
+    mat.metallicity > 0.0 || mat.specular > 0.0
+}
+
+fn specular_f0(mat: &Material) -> Vec3 {
+    let dielectric = 0.08 * mat.specular;
+    (1.0 - mat.metallicity) * vec3![dielectric, dielectric, dielectric]
+        + mat.metallicity * rgb(mat.color)
+}
+
+// What the clearcoat lets through to the layers below, seen at `cos` from the normal.
+fn coat_weight(mat: &Material, cos: f32) -> f32 {
+    1.0 - 0.25 * mat.clearcoat * schlick(vec3![0.04, 0.04, 0.04], cos).x
+}
+
+// Share of the light arriving at `cos` from the normal that reaches the diffuse base, past the
+// clearcoat and what the dielectric specular lobe reflects; metals have no base.
+fn diffuse_weight(mat: &Material, cos: f32) -> f32 {
+    let f0 = 0.08 * mat.specular;
+    let fresnel = match mat.specular > 0.0 {
+        true => schlick(vec3![f0, f0, f0], cos).x,
+        false => 0.0,
+    };
+    (1.0 - mat.metallicity) * (1.0 - fresnel) * coat_weight(mat, cos)
+}
+
+// Reflected f * cos(theta_i) and the pdf of `sample` picking `wi`, both in the local frame.
+fn eval_local(mat: &Material, wo: Vec3, wi: Vec3) -> (Vec3, f32) {
+    if wo.z <= 0.0 || wi.z <= 0.0 {
+        return (vec3![], 0.0);
+    }
+    let lobes = Lobes::from(mat, wo);
+    let m = (wo + wi).normalize();
+
+    let diffuse = diffuse_weight(mat, wo.z) / PI * wi.z * rgb(mat.color);
+    let mut value = diffuse;
+    let mut pdf = lobes.diffuse * wi.z / PI;
+
+    let mut microfacet = |f0: Vec3, weight: f32, alpha: f32, probability: f32| {
+        let d = ggx_d(m.z, alpha);
+        let g1o = ggx_g1(wo.z, alpha);
+        let f = schlick(f0, wi.dot(m));
+        value += weight * d * g1o * ggx_g1(wi.z, alpha) / (4.0 * wo.z) * f;
+        pdf += probability * g1o * d / (4.0 * wo.z);
+    };
+    if has_specular(mat) {
+        let weight = coat_weight(mat, wo.z);
+        microfacet(
+            specular_f0(mat),
+            weight,
+            alpha(mat.roughness),
+            lobes.specular,
+        );
+    }
+    if mat.clearcoat > 0.0 {
+        let f0 = vec3![0.04, 0.04, 0.04];
+        let alpha = alpha(mat.clearcoat_roughness);
+        microfacet(f0, 0.25 * mat.clearcoat, alpha, lobes.clearcoat);
+    }
+    (value, pdf)
+}
+
+// f * cos(theta_i) towards `wi` and the pdf of `sample` picking it, for light sampling.
+pub fn eval(mat: &Material, norm: Vec3, wo: Vec3, wi: Vec3) -> (Color, f32) {
+    let frame = Frame::from(norm);
+    let (value, pdf) = eval_local(mat, frame.to_local(wo), frame.to_local(wi));
+    let opaque = 1.0 - mat.transparency;
+    (to_color(opaque * value), opaque * pdf)
+}
+
+pub fn sample(
+    mat: &Material,
+    norm: Vec3,
+    front_face: bool,
+    wo: Vec3,
+    rng: &mut Pcg,
+) -> Option<BsdfSample> {
+    if rng.gen::<f32>() < mat.transparency {
+        return Some(transmission(mat, norm, front_face, wo, rng));
+    }
+
+    let frame = Frame::from(norm);
+    let wo_local = frame.to_local(wo);
+    if wo_local.z <= 0.0 {
+        return None;
+    }
+    let lobes = Lobes::from(mat, wo_local);
+    let (u, u1, u2): (f32, f32, f32) = (rng.gen(), rng.gen(), rng.gen());
+    let wi_local = if u < lobes.diffuse {
+        // cosine-weighted hemisphere
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        vec3![r * phi.cos(), r * phi.sin(), (1.0 - u1).max(0.0).sqrt()]
+    } else {
+        let roughness = if u < lobes.diffuse + lobes.specular {
+            mat.roughness
+        } else {
+            mat.clearcoat_roughness
+        };
+        let m = sample_ggx_vndf(wo_local, alpha(roughness), u1, u2);
+        reflect(wo_local, m)
+    };
+
+    let (value, pdf) = eval_local(mat, wo_local, wi_local);
+    if pdf <= 0.0 {
+        return None;
+    }
+    Some(BsdfSample {
+        dir: frame.to_world(wi_local).normalize(),
+        weight: to_color(value / pdf),
+        pdf: (1.0 - mat.transparency) * pdf,
+        delta: false,
+    })
+}
+
+// Glass: reflect or refract about a GGX-distributed microfacet normal, picking one by the
+// Schlick approximation of the Fresnel term and always reflecting past the critical angle.
+fn transmission(
+    mat: &Material,
+    norm: Vec3,
+    front_face: bool,
+    wo: Vec3,
+    rng: &mut Pcg,
+) -> BsdfSample {
+    let frame = Frame::from(norm);
+    let wo_local = frame.to_local(wo);
+    let m = if mat.roughness > 0.0 && wo_local.z > 0.0 {
+        let m = sample_ggx_vndf(wo_local, alpha(mat.roughness), rng.gen(), rng.gen());
+        frame.to_world(m).normalize()
+    } else {
+        norm
+    };
+
+    let eta = if front_face { 1.0 / mat.ior } else { mat.ior };
+    let cos_i = wo.dot(m).clamp(0.0, 1.0);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    let r0 = ((1.0 - eta) / (1.0 + eta)).powi(2);
+    let fresnel = r0 + (1.0 - r0) * (1.0 - cos_i).powi(5);
+
+    let dir = if sin2_t > 1.0 || rng.gen::<f32>() < fresnel {
+        reflect(wo, m)
+    } else {
+        ((eta * cos_i - (1.0 - sin2_t).sqrt()) * m - eta * wo).normalize()
+    };
+    BsdfSample {
+        dir,
+        weight: mat.color,
+        pdf: 0.0,
+        delta: true,
+    }
+}
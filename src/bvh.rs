@@ -0,0 +1,338 @@
+use euler::{vec3, Vec3};
+
+use crate::primitives::{HitRecord, Object3d};
+use crate::utils::{axis, Ray};
+
+pub type Objects = [Box<dyn Object3d + Sync>];
+
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        min: Vec3 {
+            x: f32::INFINITY,
+            y: f32::INFINITY,
+            z: f32::INFINITY,
+        },
+        max: Vec3 {
+            x: f32::NEG_INFINITY,
+            y: f32::NEG_INFINITY,
+            z: f32::NEG_INFINITY,
+        },
+    };
+
+    pub fn from(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+    pub fn from_points(points: &[Vec3]) -> Self {
+        points
+            .iter()
+            .fold(Self::EMPTY, |bounds, &p| bounds.union(Self::from(p, p)))
+    }
+    pub fn union(self, other: Aabb) -> Self {
+        Self {
+            min: vec3![
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z)
+            ],
+            max: vec3![
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z)
+            ],
+        }
+    }
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+    pub fn area(&self) -> f32 {
+        let d = self.max - self.min;
+        if d.x < 0.0 || d.y < 0.0 || d.z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+    // Slab test, returns the distance at which the ray enters the box.
+    fn hit(&self, ray: &Ray, inv_dir: Vec3, t_max: f32) -> Option<f32> {
+        let mut t0 = f32::NEG_INFINITY;
+        let mut t1 = t_max;
+        for i in 0..3 {
+            let inv = axis(inv_dir, i);
+            let pos = axis(ray.pos, i);
+            let near = (axis(self.min, i) - pos) * inv;
+            let far = (axis(self.max, i) - pos) * inv;
+            let (near, far) = if inv < 0.0 { (far, near) } else { (near, far) };
+            // max/min ignore the NaN from 0 * inf on rays parallel to a slab
+            t0 = t0.max(near);
+            t1 = t1.min(far);
+            if t0 > t1 {
+                return None;
+            }
+        }
+        if t1 < 0.0 {
+            return None;
+        }
+        Some(t0)
+    }
+}
+
+struct Node {
+    bounds: Aabb,
+    // leaf: range in `order`, inner: `start` is the right child, the left one follows the node
+    start: usize,
+    count: usize,
+}
+
+pub struct Bvh {
+    nodes: Vec<Node>,
+    order: Vec<usize>,
+}
+
+const BINS: usize = 12;
+const LEAF_SIZE: usize = 2;
+const TRAVERSAL_COST: f32 = 1.0;
+
+impl Bvh {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![],
+            order: vec![],
+        }
+    }
+
+    pub fn from(objects: &Objects) -> Self {
+        let bounds: Vec<Aabb> = objects.iter().map(|obj| obj.bounds()).collect();
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(objects.len() * 2),
+            order: (0..objects.len()).collect(),
+        };
+        if !objects.is_empty() {
+            bvh.build(&bounds, 0, objects.len());
+        }
+        bvh
+    }
+
+    fn build(&mut self, bounds: &[Aabb], start: usize, end: usize) -> usize {
+        let index = self.nodes.len();
+        let node_bounds = self.order[start..end]
+            .iter()
+            .fold(Aabb::EMPTY, |b, &i| b.union(bounds[i]));
+        self.nodes.push(Node {
+            bounds: node_bounds,
+            start,
+            count: end - start,
+        });
+
+        let count = end - start;
+        if count <= LEAF_SIZE {
+            return index;
+        }
+
+        let centroids = self.order[start..end].iter().fold(Aabb::EMPTY, |b, &i| {
+            let c = bounds[i].center();
+            b.union(Aabb::from(c, c))
+        });
+
+        // binned surface area heuristic over all three axes
+        let mut best: Option<(usize, usize, f32)> = None;
+        for ax in 0..3 {
+            let lo = axis(centroids.min, ax);
+            let extent = axis(centroids.max, ax) - lo;
+            if extent <= 0.0 {
+                continue;
+            }
+            let bin_of = |i: usize| {
+                let c = axis(bounds[i].center(), ax);
+                (((c - lo) / extent * BINS as f32) as usize).min(BINS - 1)
+            };
+
+            let mut bin_bounds = [Aabb::EMPTY; BINS];
+            let mut bin_count = [0; BINS];
+            for &i in &self.order[start..end] {
+                let bin = bin_of(i);
+                bin_bounds[bin] = bin_bounds[bin].union(bounds[i]);
+                bin_count[bin] += 1;
+            }
+
+            let mut left_area = [0.0; BINS];
+            let mut left_count = [0; BINS];
+            let (mut acc, mut n) = (Aabb::EMPTY, 0);
+            for bin in 0..BINS - 1 {
+                acc = acc.union(bin_bounds[bin]);
+                n += bin_count[bin];
+                left_area[bin] = acc.area();
+                left_count[bin] = n;
+            }
+            let (mut acc, mut n) = (Aabb::EMPTY, 0);
+            for bin in (1..BINS).rev() {
+                acc = acc.union(bin_bounds[bin]);
+                n += bin_count[bin];
+                let cost = left_area[bin - 1] * left_count[bin - 1] as f32 + acc.area() * n as f32;
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    best = Some((ax, bin, cost));
+                }
+            }
+        }
+
+        let leaf_cost = node_bounds.area() * count as f32;
+        let (ax, split) = match best {
+            Some((ax, bin, cost)) if TRAVERSAL_COST * node_bounds.area() + cost < leaf_cost => {
+                (ax, bin)
+            }
+            _ => return index,
+        };
+
+        let lo = axis(centroids.min, ax);
+        let extent = axis(centroids.max, ax) - lo;
+        let mut mid = start;
+        for i in start..end {
+            let c = axis(bounds[self.order[i]].center(), ax);
+            if ((((c - lo) / extent * BINS as f32) as usize).min(BINS - 1)) < split {
+                self.order.swap(i, mid);
+                mid += 1;
+            }
+        }
+        if mid == start || mid == end {
+            return index;
+        }
+
+        self.build(bounds, start, mid);
+        let right = self.build(bounds, mid, end);
+        self.nodes[index].start = right;
+        self.nodes[index].count = 0;
+        index
+    }
+
+    // Returns the closest hit with `t_min < t < t_max`.
+    pub fn closest(
+        &self,
+        objects: &Objects,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<HitRecord> {
+        let mut best = None;
+        self.traverse(ray, t_max, |i, t_max| {
+            if let Some(mut hit) = objects[i].hit(ray, t_min, *t_max) {
+                *t_max = hit.t;
+                hit.object = i;
+                best = Some(hit);
+            }
+            false
+        });
+        best
+    }
+
+    // Returns whether anything is hit with `t_min < t < t_max`.
+    pub fn any(&self, objects: &Objects, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        let mut hit = false;
+        self.traverse(ray, t_max, |i, t_max| {
+            hit = objects[i].hit(ray, t_min, *t_max).is_some();
+            hit
+        });
+        hit
+    }
+
+    // Calls `visit` for every object in a node the ray enters before `t_max`, until it returns true.
+    // `visit` may shrink `t_max` to cull farther nodes.
+    fn traverse(&self, ray: &Ray, t_max: f32, mut visit: impl FnMut(usize, &mut f32) -> bool) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let inv_dir = vec3![1.0 / ray.dir.x, 1.0 / ray.dir.y, 1.0 / ray.dir.z];
+        let mut t_max = t_max;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.bounds.hit(ray, inv_dir, t_max).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                for &i in &self.order[node.start..node.start + node.count] {
+                    if visit(i, &mut t_max) {
+                        return;
+                    }
+                }
+                continue;
+            }
+            let (left, right) = (index + 1, node.start);
+            let t_left = self.nodes[left].bounds.hit(ray, inv_dir, t_max);
+            let t_right = self.nodes[right].bounds.hit(ray, inv_dir, t_max);
+            // push the farther child first so the nearer one is visited first
+            match (t_left, t_right) {
+                (Some(l), Some(r)) if l < r => stack.extend([right, left]),
+                (Some(_), Some(_)) => stack.extend([left, right]),
+                (Some(_), None) => stack.push(left),
+                (None, Some(_)) => stack.push(right),
+                (None, None) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::primitives::Trig;
+    use crate::rng::Pcg;
+    use crate::utils::Material;
+    use rand::Rng;
+
+    fn point(rng: &mut Pcg, size: f32) -> Vec3 {
+        vec3![
+            rng.gen_range(-size..size),
+            rng.gen_range(-size..size),
+            rng.gen_range(-size..size)
+        ]
+    }
+
+    #[test]
+    fn closest_matches_linear_scan() {
+        let mut rng = Pcg::from(1, 0, 0);
+        let mat = Material::from(Color::WHITE, 0.0, 0.0, Color::BLACK);
+        let objects: Vec<Box<dyn Object3d + Sync>> = (0..500)
+            .map(|_| {
+                let v0 = point(&mut rng, 10.0);
+                let (v1, v2) = (v0 + point(&mut rng, 1.5), v0 + point(&mut rng, 1.5));
+                Box::new(Trig::from(v0, v1, v2, mat)) as Box<dyn Object3d + Sync>
+            })
+            .collect();
+        let bvh = Bvh::from(&objects);
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let ray = Ray::from(point(&mut rng, 12.0), point(&mut rng, 1.0));
+            let linear =
+                objects
+                    .iter()
+                    .enumerate()
+                    .fold(None, |best: Option<(f32, usize)>, (i, obj)| {
+                        let t_max = best.map_or(f32::INFINITY, |(t, _)| t);
+                        obj.hit(&ray, 1e-4, t_max).map(|hit| (hit.t, i)).or(best)
+                    });
+            let accelerated = bvh
+                .closest(&objects, &ray, 1e-4, f32::INFINITY)
+                .map(|hit| (hit.t, hit.object));
+            match (linear, accelerated) {
+                (Some((t, i)), Some((bvh_t, bvh_i))) => {
+                    assert!((t - bvh_t).abs() <= 1e-5 * t.max(1.0), "{} vs {}", t, bvh_t);
+                    assert_eq!(i, bvh_i);
+                    hits += 1;
+                }
+                (None, None) => {}
+                (linear, accelerated) => panic!("{:?} vs {:?}", linear, accelerated),
+            }
+            assert_eq!(
+                bvh.any(&objects, &ray, 1e-4, f32::INFINITY),
+                linear.is_some()
+            );
+        }
+        assert!(hits > 100, "only {} rays hit", hits);
+    }
+}
@@ -61,17 +61,61 @@ impl Div<f32> for Color {
     }
 }
 
+// Linear to sRGB transfer function.
+fn srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
 #[allow(clippy::eq_op)]
 impl Color {
     fn from(r: f32, g: f32, b: f32, a: f32) -> Self {
         Self([r, g, b, a])
     }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "LIGHTGRAY" => Some(Self::LIGHTGRAY),
+            "GRAY" => Some(Self::GRAY),
+            "DARKGRAY" => Some(Self::DARKGRAY),
+            "YELLOW" => Some(Self::YELLOW),
+            "GOLD" => Some(Self::GOLD),
+            "ORANGE" => Some(Self::ORANGE),
+            "PINK" => Some(Self::PINK),
+            "RED" => Some(Self::RED),
+            "MAROON" => Some(Self::MAROON),
+            "GREEN" => Some(Self::GREEN),
+            "LIME" => Some(Self::LIME),
+            "DARKGREEN" => Some(Self::DARKGREEN),
+            "SKYBLUE" => Some(Self::SKYBLUE),
+            "BLUE" => Some(Self::BLUE),
+            "DARKBLUE" => Some(Self::DARKBLUE),
+            "PURPLE" => Some(Self::PURPLE),
+            "VIOLET" => Some(Self::VIOLET),
+            "DARKPURPLE" => Some(Self::DARKPURPLE),
+            "BEIGE" => Some(Self::BEIGE),
+            "BROWN" => Some(Self::BROWN),
+            "DARKBROWN" => Some(Self::DARKBROWN),
+            "WHITE" => Some(Self::WHITE),
+            "BLACK" => Some(Self::BLACK),
+            "BLANK" => Some(Self::BLANK),
+            "MAGENTA" => Some(Self::MAGENTA),
+            "RAYWHITE" => Some(Self::RAYWHITE),
+            _ => None,
+        }
+    }
+    // 8-bit sRGB of a linear color in [0, 1], see tonemap.rs to get there. Alpha stays linear.
     pub fn into_u8(self) -> [u8; 4] {
+        let [r, g, b, a] = self.0;
+        let quantize = |c: f32| (c * 255.0 + 0.5).clamp(0.0, 255.0) as u8;
         [
-            (self.0[0] * 255.0).max(0.0).min(255.0) as u8,
-            (self.0[1] * 255.0).max(0.0).min(255.0) as u8,
-            (self.0[2] * 255.0).max(0.0).min(255.0) as u8,
-            (self.0[3] * 255.0).max(0.0).min(255.0) as u8,
+            quantize(srgb(r)),
+            quantize(srgb(g)),
+            quantize(srgb(b)),
+            quantize(a),
         ]
     }
     pub const LIGHTGRAY: Color =
@@ -103,4 +147,3 @@ impl Color {
     pub const MAGENTA: Color = Color([255.0 / 255.0, 0.0 / 255.0, 255.0 / 255.0, 255.0 / 255.0]);
     pub const RAYWHITE: Color = Color([245.0 / 255.0, 245.0 / 255.0, 245.0 / 255.0, 255.0 / 255.0]);
 }
-
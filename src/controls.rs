@@ -0,0 +1,99 @@
+use euler::{vec3, Vec3};
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+use std::f32::consts::FRAC_PI_2;
+use std::time::Instant;
+
+use crate::utils::Camera;
+
+pub const HELP: &str =
+    "controls: WASD move, Q/E down/up, drag to orbit, scroll to zoom, N to toggle the denoiser, \
+     P to save the image, comma/period to step the scene time, space to play it";
+
+// Share of the orbit distance moved per second a key is held.
+const MOVE_SPEED: f32 = 1.2;
+// Longest step a single update moves, so a stalled frame doesn't throw the camera away.
+const MAX_STEP: f32 = 1.0 / 30.0;
+// Radians per pixel of mouse drag.
+const ORBIT_SPEED: f32 = 0.005;
+// Zoom factor per scroll step.
+const ZOOM_SPEED: f32 = 0.9;
+
+// Orbit camera around a target point, +z up like the scenes.
+pub struct Orbit {
+    target: Vec3,
+    distance: f32,
+    // around +z and above the horizontal plane, radians
+    yaw: f32,
+    pitch: f32,
+    // when input was last applied, movement is scaled by the time since
+    last: Instant,
+}
+
+impl Orbit {
+    pub fn from(camera: &Camera) -> Self {
+        let offset = camera.pos() - camera.target();
+        let distance = offset.length().max(1e-3);
+        Self {
+            target: camera.target(),
+            distance,
+            yaw: offset.y.atan2(offset.x),
+            pitch: (offset.z / distance).clamp(-1.0, 1.0).asin(),
+            last: Instant::now(),
+        }
+    }
+
+    fn pos(&self) -> Vec3 {
+        let (sy, cy) = self.yaw.sin_cos();
+        let (sp, cp) = self.pitch.sin_cos();
+        self.target + self.distance * vec3![cp * cy, cp * sy, sp]
+    }
+
+    // Applies this frame's input, returns whether the view changed.
+    pub fn update(&mut self, input: &WinitInputHelper) -> bool {
+        let mut changed = false;
+        let now = Instant::now();
+        let step = now.duration_since(self.last).as_secs_f32().min(MAX_STEP);
+        self.last = now;
+
+        // WASD moves in the horizontal plane relative to where the camera looks
+        let forward = vec3![-self.yaw.cos(), -self.yaw.sin(), 0.0];
+        let right = vec3![forward.y, -forward.x, 0.0];
+        let up = vec3![0.0, 0.0, 1.0];
+        let keys = [
+            (VirtualKeyCode::W, forward),
+            (VirtualKeyCode::S, vec3![] - forward),
+            (VirtualKeyCode::D, right),
+            (VirtualKeyCode::A, vec3![] - right),
+            (VirtualKeyCode::E, up),
+            (VirtualKeyCode::Q, vec3![] - up),
+        ];
+        for (key, dir) in keys {
+            if input.key_held(key) {
+                self.target += MOVE_SPEED * step * self.distance * dir;
+                changed = true;
+            }
+        }
+
+        let (dx, dy) = input.mouse_diff();
+        if input.mouse_held(0) && (dx != 0.0 || dy != 0.0) {
+            self.yaw -= dx * ORBIT_SPEED;
+            // stay short of the poles where the up vector degenerates
+            self.pitch = (self.pitch + dy * ORBIT_SPEED).clamp(-FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
+            changed = true;
+        }
+
+        let scroll = input.scroll_diff();
+        if scroll != 0.0 {
+            self.distance *= ZOOM_SPEED.powf(scroll);
+            changed = true;
+        }
+        changed
+    }
+
+    // `camera` with the orbit's view, keeping its lens and field of view.
+    pub fn apply(&self, camera: Camera) -> Camera {
+        camera.with_view(self.pos(), self.target, vec3![0.0, 0.0, 1.0])
+    }
+}
@@ -0,0 +1,119 @@
+use rayon::prelude::*;
+
+use crate::aov::Aov;
+use crate::color::Color;
+
+// Output variables the denoiser is guided by.
+pub const GUIDES: [Aov; 4] = [Aov::Albedo, Aov::Normal, Aov::Depth, Aov::Emission];
+
+// Passes of the filter, each one twice as wide as the one before.
+const ITERATIONS: u32 = 5;
+// B3 spline weights from the center outwards.
+const KERNEL: [f32; 3] = [3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+// Sharpness of the edge stopping functions, the color one loosens as noise gets filtered away.
+const PHI_NORMAL: f32 = 64.0;
+const PHI_DEPTH: f32 = 1.0;
+const PHI_COLOR: f32 = 0.05;
+
+// First-hit buffers laid out like the image, see `GUIDES`.
+pub struct Guides<'a> {
+    pub albedo: &'a [Color],
+    pub normal: &'a [Color],
+    pub depth: &'a [Color],
+    pub emission: &'a [Color],
+}
+
+fn dot(a: [f32; 4], b: [f32; 4]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn luminance([r, g, b]: [f32; 3]) -> f32 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+// Edge-avoiding à-trous wavelet filter (Dammertz et al. 2010). Emission and albedo are taken
+// out first so only the lighting gets blurred, and the blur stops at changes of normal, depth
+// and lighting.
+pub fn denoise(width: usize, height: usize, color: &[Color], guides: &Guides) -> Vec<Color> {
+    // where the albedo is black nothing can be divided out, the color is filtered as it is
+    let albedo: Vec<[f32; 3]> = guides
+        .albedo
+        .iter()
+        .map(|a| [0, 1, 2].map(|c| if a.0[c] > 1e-3 { a.0[c] } else { 1.0 }))
+        .collect();
+    let mut lighting: Vec<[f32; 3]> = (0..color.len())
+        .map(|p| {
+            [0, 1, 2].map(|c| (color[p].0[c] - guides.emission[p].0[c]).max(0.0) / albedo[p][c])
+        })
+        .collect();
+
+    // how fast depth changes around each pixel, tolerates the slope of tilted surfaces
+    let depth = |x: usize, y: usize| guides.depth[y * width + x].0[0];
+    let slope: Vec<f32> = (0..color.len())
+        .map(|p| {
+            let (x, y) = (p % width, p / width);
+            let dx = depth((x + 1).min(width - 1), y) - depth(x.saturating_sub(1), y);
+            let dy = depth(x, (y + 1).min(height - 1)) - depth(x, y.saturating_sub(1));
+            0.5 * dx.abs().max(dy.abs())
+        })
+        .collect();
+
+    for i in 0..ITERATIONS {
+        let step = 1 << i;
+        let phi_color = PHI_COLOR * 0.5f32.powi(i as i32);
+        lighting = (0..color.len())
+            .into_par_iter()
+            .map(|p| {
+                let (x, y) = ((p % width) as isize, (p / width) as isize);
+                let normal = guides.normal[p].0;
+                let z = guides.depth[p].0[0];
+                // compared after squeezing into [0, 1) so bright outliers don't dominate
+                let l = luminance(lighting[p].map(|c| c / (1.0 + c)));
+
+                let (mut sum, mut total) = ([0.0; 3], 0.0);
+                for dy in -2..=2isize {
+                    for dx in -2..=2isize {
+                        let (qx, qy) = (x + dx * step, y + dy * step);
+                        if qx < 0 || qy < 0 || qx >= width as isize || qy >= height as isize {
+                            continue;
+                        }
+                        let q = qy as usize * width + qx as usize;
+                        let other = guides.normal[q].0;
+                        // accumulated normals are shorter than one where surfaces meet
+                        let cos = dot(normal, other)
+                            / (dot(normal, normal) * dot(other, other)).sqrt().max(1e-8);
+                        // rays that miss have no normal and only mix with each other
+                        let misses = [normal, other].map(|n| n[..3] == [0.0; 3]);
+                        let w_normal = match misses {
+                            [true, true] => 1.0,
+                            [false, false] => cos.max(0.0).powf(PHI_NORMAL),
+                            _ => 0.0,
+                        };
+                        let offset = (dx.abs().max(dy.abs()) * step) as f32;
+                        let w_depth = (-(z - guides.depth[q].0[0]).abs()
+                            / (PHI_DEPTH * slope[p] * offset + 1e-4))
+                            .exp();
+                        let lq = luminance(lighting[q].map(|c| c / (1.0 + c)));
+                        let w_color = (-(l - lq) * (l - lq) / phi_color).exp();
+
+                        let h = KERNEL[dx.unsigned_abs()] * KERNEL[dy.unsigned_abs()];
+                        let w = h * w_normal * w_depth * w_color;
+                        for c in 0..3 {
+                            sum[c] += w * lighting[q][c];
+                        }
+                        total += w;
+                    }
+                }
+                sum.map(|s| s / total)
+            })
+            .collect();
+    }
+
+    (0..color.len())
+        .map(|p| {
+            let [r, g, b] =
+                [0, 1, 2].map(|c| lighting[p][c] * albedo[p][c] + guides.emission[p].0[c]);
+            Color([r, g, b, color[p].0[3]])
+        })
+        .collect()
+}
@@ -0,0 +1,312 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::color::Color;
+
+// Bits per channel of the written pixels.
+#[derive(Clone, Copy)]
+pub enum Precision {
+    Half,
+    Float,
+}
+
+// One image of a file, its channels are named `name.channel`, or just `channel` without a name.
+pub struct Layer<'a> {
+    pub name: &'a str,
+    pub pixels: &'a [Color],
+    // channel names and the color components they hold
+    pub channels: &'a [(&'a str, usize)],
+    pub precision: Precision,
+}
+
+// The channels of a beauty image.
+pub const RGBA: &[(&str, usize)] = &[("R", 0), ("G", 1), ("B", 2), ("A", 3)];
+
+// Scanline OpenEXR without compression, linear with rows from the top.
+pub fn write(path: &Path, width: usize, height: usize, layers: &[Layer]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&encode(width, height, layers))?;
+    file.flush()
+}
+
+fn attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    for text in [name, kind] {
+        out.extend_from_slice(text.as_bytes());
+        out.push(0);
+    }
+    out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+impl Precision {
+    // pixel type in the header and bytes per value
+    fn format(self) -> (i32, usize) {
+        match self {
+            Precision::Half => (1, 2),
+            Precision::Float => (2, 4),
+        }
+    }
+}
+
+pub fn encode(width: usize, height: usize, layers: &[Layer]) -> Vec<u8> {
+    // channels are stored in alphabetical order
+    let mut channels: Vec<(String, &Layer, usize)> = layers
+        .iter()
+        .flat_map(|layer| {
+            layer.channels.iter().map(move |&(channel, component)| {
+                let name = match layer.name {
+                    "" => channel.to_string(),
+                    name => format!("{}.{}", name, channel),
+                };
+                (name, layer, component)
+            })
+        })
+        .collect();
+    channels.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut out = vec![];
+    out.extend_from_slice(&20000630i32.to_le_bytes());
+    out.extend_from_slice(&2i32.to_le_bytes());
+
+    let mut list = vec![];
+    for (name, layer, _) in &channels {
+        list.extend_from_slice(name.as_bytes());
+        list.push(0);
+        list.extend_from_slice(&layer.precision.format().0.to_le_bytes());
+        // linear flag and reserved bytes, then x and y sampling
+        list.extend_from_slice(&[0; 4]);
+        list.extend_from_slice(&1i32.to_le_bytes());
+        list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    list.push(0);
+    attribute(&mut out, "channels", "chlist", &list);
+    attribute(&mut out, "compression", "compression", &[0]);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    attribute(&mut out, "dataWindow", "box2i", &window);
+    attribute(&mut out, "displayWindow", "box2i", &window);
+    attribute(&mut out, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut out, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut out, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut out, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    out.push(0);
+
+    // offsets of every scanline from the start of the file, then the scanlines: their row,
+    // byte count and one channel after the other
+    let line_size: usize = channels
+        .iter()
+        .map(|(_, layer, _)| width * layer.precision.format().1)
+        .sum();
+    let table_end = out.len() + height * 8;
+    for y in 0..height {
+        let offset = table_end + y * (8 + line_size);
+        out.extend_from_slice(&(offset as u64).to_le_bytes());
+    }
+    for y in 0..height {
+        out.extend_from_slice(&(y as i32).to_le_bytes());
+        out.extend_from_slice(&(line_size as i32).to_le_bytes());
+        for &(_, layer, component) in &channels {
+            for pixel in &layer.pixels[y * width..(y + 1) * width] {
+                let value = pixel.0[component];
+                match layer.precision {
+                    Precision::Half => out.extend_from_slice(&to_half(value).to_le_bytes()),
+                    Precision::Float => out.extend_from_slice(&value.to_le_bytes()),
+                }
+            }
+        }
+    }
+    out
+}
+
+// Nearest IEEE half, ties to even; too large values become infinite.
+fn to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        // subnormal, the implicit leading bit becomes explicit
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let half = mantissa >> shift;
+        let round = 1 << (shift - 1);
+        let up = mantissa & round != 0 && (mantissa & (round - 1) != 0 || half & 1 != 0);
+        return sign | (half + up as u32) as u16;
+    }
+    // a carry out of the mantissa correctly bumps the exponent
+    let half = (exponent as u32) << 10 | mantissa >> 13;
+    let rest = mantissa & 0x1fff;
+    let up = rest > 0x1000 || (rest == 0x1000 && half & 1 != 0);
+    sign | (half + up as u32) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_rounding() {
+        assert_eq!(to_half(1.0), 0x3c00);
+        assert_eq!(to_half(-2.0), 0xc000);
+        assert_eq!(to_half(0.5), 0x3800);
+        assert_eq!(to_half(0.0), 0x0000);
+        assert_eq!(to_half(-0.0), 0x8000);
+        // halfway between two halves goes to the even one
+        assert_eq!(to_half(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(to_half(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+        assert_eq!(to_half(1.0 + 1.1 * 2f32.powi(-11)), 0x3c01);
+        // a carry out of the mantissa moves on to the next power of two
+        assert_eq!(to_half(2.0 - 2f32.powi(-12)), 0x4000);
+    }
+
+    #[test]
+    fn half_subnormals() {
+        assert_eq!(to_half(2f32.powi(-14)), 0x0400);
+        assert_eq!(to_half(2f32.powi(-15)), 0x0200);
+        assert_eq!(to_half(2f32.powi(-24)), 0x0001);
+        assert_eq!(to_half(-2f32.powi(-24)), 0x8001);
+        assert_eq!(to_half(2f32.powi(-25)), 0x0000);
+        assert_eq!(to_half(1.5 * 2f32.powi(-24)), 0x0002);
+        assert_eq!(to_half(2f32.powi(-30)), 0x0000);
+        // the largest subnormal rounds up into the smallest normal
+        assert_eq!(to_half(2f32.powi(-14) - 2f32.powi(-26)), 0x0400);
+    }
+
+    #[test]
+    fn half_overflow_and_nan() {
+        assert_eq!(to_half(65504.0), 0x7bff);
+        assert_eq!(to_half(65519.0), 0x7bff);
+        assert_eq!(to_half(65520.0), 0x7c00);
+        assert_eq!(to_half(1e10), 0x7c00);
+        assert_eq!(to_half(-1e10), 0xfc00);
+        assert_eq!(to_half(f32::INFINITY), 0x7c00);
+        assert_eq!(to_half(f32::NEG_INFINITY), 0xfc00);
+        let nan = to_half(f32::NAN);
+        assert_eq!(nan & 0x7c00, 0x7c00);
+        assert_ne!(nan & 0x3ff, 0);
+    }
+
+    fn read_i32(data: &[u8], pos: usize) -> i32 {
+        i32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+    }
+
+    fn read_string(data: &[u8], pos: &mut usize) -> String {
+        let end = *pos + data[*pos..].iter().position(|&b| b == 0).unwrap();
+        let text = String::from_utf8(data[*pos..end].to_vec()).unwrap();
+        *pos = end + 1;
+        text
+    }
+
+    #[test]
+    fn layout() {
+        let (width, height) = (3, 2);
+        let pixels: Vec<Color> = (0..width * height)
+            .map(|i| Color([i as f32, 0.5, 0.25, 1.0]))
+            .collect();
+        let depth: Vec<Color> = (0..width * height)
+            .map(|i| Color([10.0 + i as f32, 0.0, 0.0, 1.0]))
+            .collect();
+        let layers = [
+            Layer {
+                name: "",
+                pixels: &pixels,
+                channels: RGBA,
+                precision: Precision::Half,
+            },
+            Layer {
+                name: "depth",
+                pixels: &depth,
+                channels: &[("Z", 0)],
+                precision: Precision::Float,
+            },
+        ];
+        let data = encode(width, height, &layers);
+        assert_eq!(read_i32(&data, 0), 20000630);
+        assert_eq!(read_i32(&data, 4), 2);
+
+        // attributes up to the empty name ending the header
+        let mut pos = 8;
+        let mut attributes = vec![];
+        loop {
+            let name = read_string(&data, &mut pos);
+            if name.is_empty() {
+                break;
+            }
+            let kind = read_string(&data, &mut pos);
+            let size = read_i32(&data, pos) as usize;
+            attributes.push((name, kind, data[pos + 4..pos + 4 + size].to_vec()));
+            pos += 4 + size;
+        }
+        let names: Vec<&str> = attributes
+            .iter()
+            .map(|(name, _, _)| name.as_str())
+            .collect();
+        for required in [
+            "channels",
+            "compression",
+            "dataWindow",
+            "displayWindow",
+            "lineOrder",
+            "pixelAspectRatio",
+            "screenWindowCenter",
+            "screenWindowWidth",
+        ] {
+            assert!(names.contains(&required), "missing {}", required);
+        }
+        let window = &attributes.iter().find(|a| a.0 == "dataWindow").unwrap().2;
+        let window: Vec<i32> = (0..4).map(|i| read_i32(window, 4 * i)).collect();
+        assert_eq!(window, [0, 0, 2, 1]);
+
+        // channels sorted by name, each with its pixel type
+        let list = &attributes.iter().find(|a| a.0 == "channels").unwrap().2;
+        let (mut at, mut channels) = (0, vec![]);
+        while list[at] != 0 {
+            let name = read_string(list, &mut at);
+            channels.push((name, read_i32(list, at)));
+            at += 16;
+        }
+        let expected = [("A", 1), ("B", 1), ("G", 1), ("R", 1), ("depth.Z", 2)];
+        let channels: Vec<(&str, i32)> = channels.iter().map(|(n, t)| (n.as_str(), *t)).collect();
+        assert_eq!(channels, expected);
+
+        // the offset table points at each scanline: its row, its size, then the channels
+        let line_size = width * (4 * 2 + 4);
+        for y in 0..height {
+            let offset = u64::from_le_bytes(data[pos + 8 * y..pos + 8 * y + 8].try_into().unwrap());
+            let offset = offset as usize;
+            assert_eq!(offset, pos + 8 * height + y * (8 + line_size));
+            assert_eq!(read_i32(&data, offset), y as i32);
+            assert_eq!(read_i32(&data, offset + 4), line_size as i32);
+            let line = &data[offset + 8..offset + 8 + line_size];
+            let half = |channel: usize, x: usize| {
+                let at = (channel * width + x) * 2;
+                u16::from_le_bytes([line[at], line[at + 1]])
+            };
+            let x = 1;
+            let i = y * width + x;
+            assert_eq!(half(0, x), to_half(1.0));
+            assert_eq!(half(1, x), to_half(0.25));
+            assert_eq!(half(2, x), to_half(0.5));
+            assert_eq!(half(3, x), to_half(i as f32));
+            let at = 4 * width * 2 + x * 4;
+            let z = f32::from_le_bytes(line[at..at + 4].try_into().unwrap());
+            assert_eq!(z, 10.0 + i as f32);
+        }
+        let end = pos + 8 * height + height * (8 + line_size);
+        assert_eq!(data.len(), end);
+    }
+}
@@ -0,0 +1,283 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::color::Color;
+
+// Radiance RGBE (.hdr) images, linear RGB, rows from the top.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[f32; 3]>,
+}
+
+// Larger images than this are taken for corrupt headers rather than allocated.
+const MAX_PIXELS: usize = 1 << 28;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+pub fn load(path: &Path) -> io::Result<Image> {
+    decode(&fs::read(path)?)
+}
+
+pub fn decode(data: &[u8]) -> io::Result<Image> {
+    let mut pos = 0;
+    let mut line = || {
+        let end = data[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid("truncated header"))?;
+        let text = String::from_utf8_lossy(&data[pos..pos + end]).into_owned();
+        pos += end + 1;
+        Ok::<String, io::Error>(text)
+    };
+
+    let magic = line()?;
+    if !magic.starts_with("#?") {
+        return Err(invalid("not a Radiance HDR file"));
+    }
+    loop {
+        let header = line()?;
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some(format) = header.strip_prefix("FORMAT=") {
+            if format.trim() != "32-bit_rle_rgbe" {
+                return Err(invalid(format!("unsupported format {}", format.trim())));
+            }
+        }
+    }
+    // only the standard orientation, rows top to bottom and columns left to right
+    let resolution = line()?;
+    let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", h, "+X", w] => (
+            h.parse::<usize>().map_err(|_| invalid("invalid height"))?,
+            w.parse::<usize>().map_err(|_| invalid("invalid width"))?,
+        ),
+        _ => return Err(invalid(format!("unsupported resolution `{}`", resolution))),
+    };
+    if width == 0 || height == 0 {
+        return Err(invalid(format!("empty image `{}`", resolution)));
+    }
+    let size = width
+        .checked_mul(height)
+        .filter(|&size| size <= MAX_PIXELS)
+        .ok_or_else(|| invalid(format!("image too large `{}`", resolution)))?;
+
+    let mut pixels = Vec::with_capacity(size);
+    let mut rest = &data[pos..];
+    let mut scanline = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        rest = read_scanline(rest, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|&rgbe| to_float(rgbe)));
+    }
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+// Writes run-length encoded RGBE, alpha has no place in the format and is dropped.
+pub fn write(path: &Path, width: usize, height: usize, pixels: &[Color]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&encode(width, height, pixels))?;
+    file.flush()
+}
+
+pub fn encode(width: usize, height: usize, pixels: &[Color]) -> Vec<u8> {
+    let mut out = format!(
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )
+    .into_bytes();
+    for row in pixels.chunks_exact(width) {
+        let scanline: Vec<[u8; 4]> = row.iter().map(|pixel| to_rgbe(pixel.0)).collect();
+        write_scanline(&mut out, &scanline);
+    }
+    out
+}
+
+fn to_rgbe([r, g, b, _]: [f32; 4]) -> [u8; 4] {
+    let max = r.max(g).max(b);
+    if max < 1e-32 || max.is_nan() {
+        return [0; 4];
+    }
+    // the shared exponent puts the largest channel in [128, 256)
+    let mut exponent = max.log2().floor() as i32 + 1;
+    if max * 2f32.powi(8 - exponent) >= 256.0 {
+        exponent += 1;
+    }
+    let scale = 2f32.powi(8 - exponent);
+    let byte = |c: f32| (c * scale).clamp(0.0, 255.0) as u8;
+    [
+        byte(r),
+        byte(g),
+        byte(b),
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+// The inverse of `read_scanline`, runs of three or more equal bytes and literal spans between.
+fn write_scanline(out: &mut Vec<u8>, scanline: &[[u8; 4]]) {
+    let width = scanline.len();
+    if !(8..0x8000).contains(&width) {
+        out.extend(scanline.iter().flatten());
+        return;
+    }
+    out.extend_from_slice(&[2, 2, (width >> 8) as u8, width as u8]);
+    for channel in 0..4 {
+        let values: Vec<u8> = scanline.iter().map(|pixel| pixel[channel]).collect();
+        let mut x = 0;
+        while x < width {
+            let run = values[x..]
+                .iter()
+                .take(127)
+                .take_while(|&&v| v == values[x])
+                .count();
+            if run >= 3 {
+                out.extend_from_slice(&[128 + run as u8, values[x]]);
+                x += run;
+                continue;
+            }
+            // literals up to where the next run starts
+            let mut end = x + 1;
+            while end < width && end - x < 128 {
+                if end + 2 < width
+                    && values[end] == values[end + 1]
+                    && values[end] == values[end + 2]
+                {
+                    break;
+                }
+                end += 1;
+            }
+            out.push((end - x) as u8);
+            out.extend_from_slice(&values[x..end]);
+            x = end;
+        }
+    }
+}
+
+fn to_float([r, g, b, e]: [u8; 4]) -> [f32; 3] {
+    if e == 0 {
+        return [0.0; 3];
+    }
+    let scale = 2f32.powi(e as i32 - (128 + 8));
+    [r as f32 * scale, g as f32 * scale, b as f32 * scale]
+}
+
+fn take(data: &[u8], n: usize) -> io::Result<(&[u8], &[u8])> {
+    if data.len() < n {
+        return Err(invalid("truncated pixel data"));
+    }
+    Ok(data.split_at(n))
+}
+
+// Decodes one scanline and returns the remaining data.
+fn read_scanline<'a>(data: &'a [u8], scanline: &mut [[u8; 4]]) -> io::Result<&'a [u8]> {
+    let width = scanline.len();
+    let run_length = (8..0x8000).contains(&width)
+        && data.len() >= 4
+        && data[0] == 2
+        && data[1] == 2
+        && ((data[2] as usize) << 8 | data[3] as usize) == width;
+    if !run_length {
+        return read_flat(data, scanline);
+    }
+
+    // each channel is stored separately as runs and literal spans
+    let mut data = &data[4..];
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let (count, rest) = take(data, 1)?;
+            data = rest;
+            let count = count[0] as usize;
+            if count > 128 {
+                let count = count - 128;
+                let (value, rest) = take(data, 1)?;
+                data = rest;
+                if x + count > width {
+                    return Err(invalid("run exceeds the scanline"));
+                }
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[channel] = value[0];
+                }
+                x += count;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err(invalid("invalid literal span"));
+                }
+                let (values, rest) = take(data, count)?;
+                data = rest;
+                for (pixel, &value) in scanline[x..x + count].iter_mut().zip(values) {
+                    pixel[channel] = value;
+                }
+                x += count;
+            }
+        }
+    }
+    Ok(data)
+}
+
+// Uncompressed pixels, with the old `1 1 1 n` repeat encoding.
+fn read_flat<'a>(mut data: &'a [u8], scanline: &mut [[u8; 4]]) -> io::Result<&'a [u8]> {
+    let mut x = 0;
+    let mut shift = 0;
+    while x < scanline.len() {
+        let (rgbe, rest) = take(data, 4)?;
+        data = rest;
+        if rgbe[..3] == [1, 1, 1] && x > 0 {
+            let count = (rgbe[3] as usize) << shift;
+            if x + count > scanline.len() {
+                return Err(invalid("run exceeds the scanline"));
+            }
+            let previous = scanline[x - 1];
+            scanline[x..x + count].fill(previous);
+            x += count;
+            shift += 8;
+        } else {
+            scanline[x] = [rgbe[0], rgbe[1], rgbe[2], rgbe[3]];
+            x += 1;
+            shift = 0;
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(resolution: &str) -> Vec<u8> {
+        format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{}\n", resolution).into_bytes()
+    }
+
+    #[test]
+    fn rejects_bad_sizes() {
+        for resolution in [
+            "-Y 0 +X 0",
+            "-Y 4 +X 0",
+            "-Y 0 +X 4",
+            "-Y 4294967296 +X 4294967296",
+        ] {
+            assert!(decode(&header(resolution)).is_err(), "{}", resolution);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let pixels: Vec<Color> = (0..6)
+            .map(|i| Color([i as f32, 0.5, 0.25 * i as f32, 1.0]))
+            .collect();
+        let image = decode(&encode(3, 2, &pixels)).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        for (pixel, color) in image.pixels.iter().zip(&pixels) {
+            for (value, expected) in pixel.iter().zip(color.0) {
+                assert!((value - expected).abs() <= 0.01 * expected.max(0.1));
+            }
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use crate::aov::Aov;
+use crate::exr::Precision;
+use crate::output::{self, Images};
+use crate::scene_file::SceneFile;
+use crate::tiles::Order;
+use crate::tonemap::{Display, ToneMap};
+use crate::tracer::{Tracer, MAX_DEPTH, MIN_DEPTH, TILE_SIZE};
+
+pub const USAGE: &str =
+    "usage: ray_tracing render [--scene FILE] [--size N] [--width N] [--height N] [--spp N]
+                          [--time T] [--end T] [--frames N] [--min-depth N] [--max-depth N]
+                          [--tonemap NAME] [--exposure EV] [--float] [--aov NAME]... [--denoise]
+                          [--tile N] [--order NAME] [--seed N]
+                          [--out FILE]...
+    --scene FILE     scene description (default: built-in scene)
+    --size N         image width and height in pixels (default 1024)
+    --width N        image width in pixels
+    --height N       image height in pixels, the vertical field of view stays the same
+    --spp N          samples per pixel to accumulate (default 64)
+    --time T         scene time, of the first frame for sequences (default 0)
+    --end T          scene time of the last frame (default: same as --time)
+    --frames N       frames spread evenly from --time to --end (default 1); the frame number
+                     replaces a run of # in output names, e.g. frame_####.png, or else is
+                     appended to them
+    --min-depth N    bounces before Russian roulette may end a path (default 3)
+    --max-depth N    bounces after which every path ends (default 64)
+    --tonemap NAME   clamp, reinhard, aces or agx (default aces)
+    --exposure EV    brightness in stops before tone mapping (default 0)
+    --float          32-bit instead of half float channels in .exr outputs
+    --tile N         side of the square tiles rendered one after the other (default 32)
+    --order NAME     tile order: spiral from the center, hilbert or scanline (default spiral)
+    --seed N         seed of the random numbers, the same seed and settings render the
+                     same image however many threads run (default 0)
+    --denoise        filter the noise out of the image, guided by what camera rays hit
+    --aov NAME       also write albedo, normal, depth, object or emission; may be repeated;
+                     as layers of .exr outputs and as separate files next to the others
+    --out FILE       output image, .png, .ppm, .exr or .hdr; may be repeated (default
+                     render.png); .exr and .hdr hold the linear radiance, .exr with alpha";
+
+pub struct Options {
+    pub scene: Option<PathBuf>,
+    pub width: usize,
+    pub height: usize,
+    pub spp: usize,
+    pub time: f32,
+    pub end: Option<f32>,
+    pub frames: usize,
+    pub min_depth: usize,
+    pub max_depth: usize,
+    pub tone_map: ToneMap,
+    pub exposure: f32,
+    pub precision: Precision,
+    pub aovs: Vec<Aov>,
+    pub denoise: bool,
+    pub tile_size: usize,
+    pub order: Order,
+    pub seed: u64,
+    pub outputs: Vec<PathBuf>,
+}
+
+impl Options {
+    // The defaults of `render`, no outputs means render.png.
+    pub fn new() -> Self {
+        Self {
+            scene: None,
+            width: 1024,
+            height: 1024,
+            spp: 64,
+            time: 0.0,
+            end: None,
+            frames: 1,
+            min_depth: MIN_DEPTH,
+            max_depth: MAX_DEPTH,
+            tone_map: ToneMap::Aces,
+            exposure: 0.0,
+            precision: Precision::Half,
+            aovs: vec![],
+            denoise: false,
+            tile_size: TILE_SIZE,
+            order: Order::Spiral,
+            seed: 0,
+            outputs: vec![],
+        }
+    }
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        Self::new().with_args(args)
+    }
+    // Overrides the options given in `args`, which the window takes as well.
+    pub fn with_args(mut self, args: &[String]) -> Result<Self, String> {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "--scene" => self.scene = Some(PathBuf::from(value()?)),
+                "--size" => {
+                    self.width = parse_value(arg, value()?)?;
+                    self.height = self.width;
+                }
+                "--width" => self.width = parse_value(arg, value()?)?,
+                "--height" => self.height = parse_value(arg, value()?)?,
+                "--spp" => self.spp = parse_value(arg, value()?)?,
+                "--time" => self.time = parse_value(arg, value()?)?,
+                "--end" => self.end = Some(parse_value(arg, value()?)?),
+                "--frames" => self.frames = parse_value(arg, value()?)?,
+                "--min-depth" => self.min_depth = parse_value(arg, value()?)?,
+                "--max-depth" => self.max_depth = parse_value(arg, value()?)?,
+                "--tonemap" => self.tone_map = parse_value(arg, value()?)?,
+                "--exposure" => self.exposure = parse_value(arg, value()?)?,
+                "--float" => self.precision = Precision::Float,
+                "--denoise" => self.denoise = true,
+                "--tile" => self.tile_size = parse_value(arg, value()?)?,
+                "--order" => self.order = parse_value(arg, value()?)?,
+                "--seed" => self.seed = parse_value(arg, value()?)?,
+                "--aov" => self.aovs.push(parse_value(arg, value()?)?),
+                "--out" => self.outputs.push(PathBuf::from(value()?)),
+                "-h" | "--help" => return Err(USAGE.to_string()),
+                _ => return Err(format!("unknown argument: {}\n{}", arg, USAGE)),
+            }
+        }
+        if self.width == 0 || self.height == 0 {
+            return Err("image size must not be zero".to_string());
+        }
+        if self.spp == 0 {
+            return Err("samples per pixel must not be zero".to_string());
+        }
+        if self.tile_size == 0 {
+            return Err("tile size must not be zero".to_string());
+        }
+        if self.frames == 0 {
+            return Err("frame count must not be zero".to_string());
+        }
+        Ok(self)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", arg, value))
+}
+
+// Scene time of frame `i` of the sequence.
+fn frame_time(options: &Options, i: usize) -> f32 {
+    let end = options.end.unwrap_or(options.time);
+    if options.frames == 1 {
+        return options.time;
+    }
+    options.time + (end - options.time) * i as f32 / (options.frames - 1) as f32
+}
+
+// `path` with the frame number in place of its run of `#`, zero-padded to the run's length,
+// or appended to the file stem when there is none.
+fn frame_path(path: &Path, frame: usize) -> PathBuf {
+    let name = path.to_string_lossy();
+    if let Some(start) = name.find('#') {
+        let len = name[start..].chars().take_while(|&c| c == '#').count();
+        let number = format!("{:0width$}", frame, width = len);
+        return PathBuf::from(format!(
+            "{}{}{}",
+            &name[..start],
+            number,
+            &name[start + len..]
+        ));
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}_{:04}.{}", stem, frame, ext.to_string_lossy()),
+        None => format!("{}_{:04}", stem, frame),
+    };
+    path.with_file_name(name)
+}
+
+pub fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let start = Instant::now();
+    let tracer = match &options.scene {
+        Some(path) => Tracer::with_source(
+            options.width,
+            options.height,
+            SceneFile::load(path)?.into_source(),
+        ),
+        None => Tracer::from(options.width, options.height),
+    };
+    let mut tracer = tracer
+        .with_depth(options.min_depth, options.max_depth)
+        .with_display(Display::from(options.tone_map, options.exposure))
+        .with_aovs(&options.aovs)
+        .with_denoise(options.denoise)
+        .with_tiles(options.tile_size, options.order)
+        .with_seed(options.seed);
+
+    // Ctrl-C stops the render and keeps the tiles that are done
+    let cancel = tracer.cancel_flag();
+    ctrlc::set_handler(move || cancel.store(true, Ordering::Relaxed))?;
+    let outputs = match options.outputs.is_empty() {
+        true => vec![PathBuf::from("render.png")],
+        false => options.outputs.clone(),
+    };
+    let mut frame = vec![0; options.width * options.height * 4];
+    for i in 0..options.frames {
+        let time = frame_time(options, i);
+        let finished = tracer.render(time, options.spp, |_, progress| {
+            print!("\rframe {}/{}: {}", i + 1, options.frames, progress);
+            io::stdout().flush().ok();
+        });
+        println!();
+
+        // denoised once for all outputs
+        let image = tracer.image();
+        tracer.write_image(&image, &mut frame);
+        for path in &outputs {
+            let path = match options.frames > 1 || path.to_string_lossy().contains('#') {
+                true => frame_path(path, i + 1),
+                false => path.clone(),
+            };
+            let images = Images {
+                width: options.width,
+                height: options.height,
+                rgba: &frame,
+                screen: &image,
+                aovs: tracer.aovs(),
+                precision: options.precision,
+            };
+            output::save(&path, &images)?;
+            println!("wrote {}", path.display());
+        }
+        if !finished {
+            return Err("render cancelled, the outputs hold the tiles done so far".into());
+        }
+    }
+
+    let elapsed = Instant::now().duration_since(start).as_secs_f32();
+    println!("render time: {:.2} s", elapsed);
+    Ok(())
+}
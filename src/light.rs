@@ -0,0 +1,177 @@
+use euler::{vec3, Vec3};
+use rand::prelude::*;
+
+use crate::color::Color;
+use crate::rng::Pcg;
+
+// Light arriving at a point from one direction, already divided by the pdf of picking it.
+pub struct Incident {
+    pub dir: Vec3,
+    // distance to the light, shadow rays stop short of it
+    pub dist: f32,
+    pub radiance: Color,
+}
+
+// Lights the scene author places explicitly. Unlike emissive objects they are not part of the
+// geometry: they are only reached by shadow rays and never show up in the image themselves.
+pub trait Light {
+    fn sample(&self, from: Vec3, rng: &mut Pcg) -> Option<Incident>;
+}
+
+// Parallel light from infinitely far away, like the sun.
+pub struct Directional {
+    // direction the light travels in
+    dir: Vec3,
+    color: Color,
+    intensity: f32,
+}
+
+impl Directional {
+    pub fn from(dir: Vec3, color: Color, intensity: f32) -> Self {
+        Self {
+            dir: dir.normalize(),
+            color,
+            intensity,
+        }
+    }
+}
+
+impl Light for Directional {
+    fn sample(&self, _from: Vec3, _rng: &mut Pcg) -> Option<Incident> {
+        Some(Incident {
+            dir: vec3![] - self.dir,
+            dist: f32::INFINITY,
+            radiance: self.color * self.intensity,
+        })
+    }
+}
+
+// Light radiating equally in all directions from a single point, falling off with distance squared.
+pub struct Point {
+    pos: Vec3,
+    color: Color,
+    intensity: f32,
+}
+
+impl Point {
+    pub fn from(pos: Vec3, color: Color, intensity: f32) -> Self {
+        Self {
+            pos,
+            color,
+            intensity,
+        }
+    }
+}
+
+impl Light for Point {
+    fn sample(&self, from: Vec3, _rng: &mut Pcg) -> Option<Incident> {
+        towards(self.pos, from, self.color * self.intensity)
+    }
+}
+
+fn towards(pos: Vec3, from: Vec3, power: Color) -> Option<Incident> {
+    let to_light = pos - from;
+    let dist2 = to_light.dot(to_light);
+    if dist2 == 0.0 {
+        return None;
+    }
+    let dist = dist2.sqrt();
+    Some(Incident {
+        dir: to_light / dist,
+        dist,
+        radiance: power * (1.0 / dist2),
+    })
+}
+
+// Point light restricted to a cone, fading out between `inner` and `outer` (half angles, radians).
+pub struct Spot {
+    pos: Vec3,
+    dir: Vec3,
+    inner: f32,
+    outer: f32,
+    color: Color,
+    intensity: f32,
+}
+
+impl Spot {
+    pub fn from(
+        pos: Vec3,
+        dir: Vec3,
+        inner: f32,
+        outer: f32,
+        color: Color,
+        intensity: f32,
+    ) -> Self {
+        Self {
+            pos,
+            dir: dir.normalize(),
+            inner: inner.min(outer),
+            outer,
+            color,
+            intensity,
+        }
+    }
+}
+
+impl Light for Spot {
+    fn sample(&self, from: Vec3, _rng: &mut Pcg) -> Option<Incident> {
+        let incident = towards(self.pos, from, self.color * self.intensity)?;
+        let cos = self.dir.dot(vec3![] - incident.dir);
+        let (cos_inner, cos_outer) = (self.inner.cos(), self.outer.cos());
+        let falloff = if cos >= cos_inner {
+            1.0
+        } else if cos <= cos_outer {
+            return None;
+        } else {
+            let x = (cos - cos_outer) / (cos_inner - cos_outer);
+            x * x * (3.0 - 2.0 * x)
+        };
+        Some(Incident {
+            radiance: incident.radiance * falloff,
+            ..incident
+        })
+    }
+}
+
+// One-sided emitting parallelogram spanned by `edge1` and `edge2` from `corner`, it shines
+// towards `edge1 x edge2`. Sampled uniformly over its area, so shadows get soft penumbras.
+pub struct Area {
+    corner: Vec3,
+    edge1: Vec3,
+    edge2: Vec3,
+    color: Color,
+    intensity: f32,
+}
+
+impl Area {
+    pub fn from(corner: Vec3, edge1: Vec3, edge2: Vec3, color: Color, intensity: f32) -> Self {
+        Self {
+            corner,
+            edge1,
+            edge2,
+            color,
+            intensity,
+        }
+    }
+}
+
+impl Light for Area {
+    fn sample(&self, from: Vec3, rng: &mut Pcg) -> Option<Incident> {
+        let point = self.corner + rng.gen::<f32>() * self.edge1 + rng.gen::<f32>() * self.edge2;
+        let cross = self.edge1.cross(self.edge2);
+        let area = cross.length();
+        if area == 0.0 {
+            return None;
+        }
+        let incident = towards(point, from, self.color * self.intensity)?;
+        // emitted radiance times the solid angle a unit of area covers, divided by the area pdf
+        let cos = (cross / area).dot(vec3![] - incident.dir);
+        if cos <= 0.0 {
+            return None;
+        }
+        Some(Incident {
+            radiance: incident.radiance * (cos * area),
+            ..incident
+        })
+    }
+}
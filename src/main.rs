@@ -1,4 +1,5 @@
 use pixels::{Error, Pixels, SurfaceTexture};
+use std::path::PathBuf;
 use std::time::Instant;
 use winit::{
     dpi::LogicalSize,
@@ -8,26 +9,116 @@ use winit::{
 };
 use winit_input_helper::WinitInputHelper;
 
+mod aov;
+mod bench;
+mod bsdf;
+mod bvh;
 mod color;
+mod controls;
+mod denoise;
+mod exr;
+mod hdr;
+mod headless;
+mod light;
+mod obj;
+mod output;
 mod primitives;
+mod rng;
 mod scene;
+mod scene_file;
+mod sky;
+mod tiles;
+mod tonemap;
 mod utils;
 
 mod tracer;
 use tracer::*;
 
-const SIDE: usize = 1024;
+const WIDTH: usize = 1024;
+const HEIGHT: usize = 1024;
 const SCALER: usize = 1;
 const TARGET_FPS: u64 = 60;
+// samples per pixel after which the viewer stops refining
+const TARGET_SPP: usize = 1024;
+// scene time per press of the time keys, and per finished pass while playing
+const TIME_STEP: f32 = 0.05;
+
+const USAGE: &str = "usage: ray_tracing [OPTIONS]
+       ray_tracing render|bench|furnace [OPTIONS]
+    Without a command opens a window that keeps refining the image. It takes the options of
+    `ray_tracing render --help` but --end and --frames: --spp is where refining stops
+    (default 1024), --time the scene time to start at and P saves the image to each --out
+    (default capture.exr).";
 
 fn main() -> Result<(), Error> {
     env_logger::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("render") {
+        let result = headless::Options::parse(&args[1..])
+            .map_err(|err| err.into())
+            .and_then(|options| headless::run(&options));
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("bench") {
+        if let Err(err) = bench::run(&args[1..]) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("furnace") {
+        if let Err(err) = bench::furnace(&args[1..]) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let options = headless::Options {
+        width: WIDTH,
+        height: HEIGHT,
+        spp: TARGET_SPP,
+        ..headless::Options::new()
+    };
+    let options = match options.with_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            std::process::exit(1);
+        }
+    };
+    let tracer = match &options.scene {
+        Some(path) => match scene_file::SceneFile::load(path) {
+            Ok(scene) => Tracer::with_source(options.width, options.height, scene.into_source()),
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        },
+        None => Tracer::from(options.width, options.height),
+    };
+    let mut tracer = tracer
+        .with_target(options.spp)
+        .with_depth(options.min_depth, options.max_depth)
+        .with_display(tonemap::Display::from(options.tone_map, options.exposure))
+        .with_aovs(&options.aovs)
+        .with_denoise(options.denoise)
+        .with_tiles(options.tile_size, options.order)
+        .with_seed(options.seed);
+    let (width, height, precision) = (options.width, options.height, options.precision);
+    let mut time = options.time;
+
     let event_loop = EventLoop::new();
     let mut input = WinitInputHelper::new();
 
     let window = {
-        let size = LogicalSize::new(SIDE as f64, SIDE as f64);
-        let scaled_size = LogicalSize::new((SIDE * SCALER) as f64, (SIDE * SCALER) as f64);
+        let size = LogicalSize::new(width as f64, height as f64);
+        let scaled_size = LogicalSize::new((width * SCALER) as f64, (height * SCALER) as f64);
         WindowBuilder::new()
             .with_title("ray tracing")
             .with_inner_size(scaled_size)
@@ -39,11 +130,19 @@ fn main() -> Result<(), Error> {
     let mut pixels = {
         let window_size = window.inner_size();
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(SIDE as u32, SIDE as u32, surface_texture)?
+        Pixels::new(width as u32, height as u32, surface_texture)?
+    };
+
+    let outputs = match options.outputs.is_empty() {
+        true => vec![PathBuf::from("capture.exr")],
+        false => options.outputs,
     };
 
-    let mut t: f32 = 0.0;
-    let mut tracer = Tracer::from(SIDE);
+    // created from the scene's camera on the first input
+    let mut orbit: Option<controls::Orbit> = None;
+    // whether the scene time moves on by itself
+    let mut playing = false;
+    println!("{}", controls::HELP);
 
     event_loop.run(move |event, _, control_flow| {
         let start_time = Instant::now();
@@ -55,7 +154,48 @@ fn main() -> Result<(), Error> {
                 return;
             }
 
-            window.request_redraw();
+            if input.key_pressed(VirtualKeyCode::N) {
+                tracer.set_denoise(!tracer.denoise());
+                window.request_redraw();
+            }
+            if input.key_pressed(VirtualKeyCode::Comma) {
+                time -= TIME_STEP;
+            }
+            if input.key_pressed(VirtualKeyCode::Period) {
+                time += TIME_STEP;
+            }
+            if input.key_pressed(VirtualKeyCode::Space) {
+                playing = !playing;
+            }
+            if input.key_pressed(VirtualKeyCode::P) {
+                let image = tracer.image();
+                let mut frame = vec![0; width * height * 4];
+                tracer.write_image(&image, &mut frame);
+                let images = output::Images {
+                    width,
+                    height,
+                    rgba: &frame,
+                    screen: &image,
+                    aovs: tracer.aovs(),
+                    precision,
+                };
+                for path in &outputs {
+                    match output::save(path, &images) {
+                        Ok(()) => println!("wrote {}", path.display()),
+                        Err(err) => eprintln!("{}: {}", path.display(), err),
+                    }
+                }
+            }
+
+            let camera = tracer.camera();
+            let view = orbit.get_or_insert_with(|| controls::Orbit::from(&camera));
+            if view.update(&input) {
+                tracer.set_view(view.apply(camera));
+            }
+
+            if !tracer.done() || playing || time != tracer.time() {
+                window.request_redraw();
+            }
 
             let elapsed_time_f32 = Instant::now().duration_since(start_time).as_secs_f32();
 
@@ -72,8 +212,23 @@ fn main() -> Result<(), Error> {
         if let Event::RedrawRequested(_) = event {
             let start_draw = Instant::now();
 
-            tracer.draw(t, pixels.frame_mut());
-            t += 0.05;
+            tracer.draw(time, pixels.frame_mut());
+            // the next frame only once this one is complete
+            if playing && tracer.samples() > 0 {
+                time += TIME_STEP;
+            }
+            let samples = match (tracer.target(), tracer.progress()) {
+                (Some(target), Some(progress)) if !tracer.done() => {
+                    format!("{}/{} samples, {}", tracer.samples(), target, progress)
+                }
+                (Some(target), _) => format!("{}/{} samples", tracer.samples(), target),
+                (None, _) => format!("{} samples", tracer.samples()),
+            };
+            window.set_title(&format!(
+                "ray tracing - t = {:.2}, {}",
+                tracer.time(),
+                samples
+            ));
 
             let draw_time = Instant::now().duration_since(start_draw).as_secs_f32();
             let fps = 1.0 / draw_time;
@@ -85,4 +240,3 @@ fn main() -> Result<(), Error> {
         }
     });
 }
-
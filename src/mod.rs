@@ -1,6 +1,23 @@
+pub mod aov;
+pub mod bench;
+pub mod bsdf;
+pub mod bvh;
 pub mod color;
+pub mod controls;
+pub mod denoise;
+pub mod exr;
+pub mod hdr;
+pub mod headless;
+pub mod light;
+pub mod obj;
+pub mod output;
 pub mod primitives;
+pub mod rng;
 pub mod scene;
+pub mod scene_file;
+pub mod sky;
+pub mod tiles;
+pub mod tonemap;
 pub mod tracer;
 pub mod utils;
 
@@ -0,0 +1,298 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use euler::{vec2, vec3, Vec2, Vec3};
+
+// Scale, then rotate around x, y and z (degrees), then translate.
+#[derive(Clone, Copy)]
+pub struct Transform {
+    pub translate: Vec3,
+    pub rotate: Vec3,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    // Blends each of translation, rotation angles and scale linearly.
+    pub fn lerp(a: &Transform, b: &Transform, f: f32) -> Transform {
+        let mix = |a: Vec3, b: Vec3| a + f * (b - a);
+        Transform {
+            translate: mix(a.translate, b.translate),
+            rotate: mix(a.rotate, b.rotate),
+            scale: mix(a.scale, b.scale),
+        }
+    }
+    // Whether the transform mirrors, which turns the winding of faces around.
+    pub fn mirrors(&self) -> bool {
+        self.scale.x * self.scale.y * self.scale.z < 0.0
+    }
+    fn rotate(&self, v: Vec3) -> Vec3 {
+        let (sx, cx) = self.rotate.x.to_radians().sin_cos();
+        let (sy, cy) = self.rotate.y.to_radians().sin_cos();
+        let (sz, cz) = self.rotate.z.to_radians().sin_cos();
+        let v = vec3![v.x, cx * v.y - sx * v.z, sx * v.y + cx * v.z];
+        let v = vec3![cy * v.x + sy * v.z, v.y, -sy * v.x + cy * v.z];
+        vec3![cz * v.x - sz * v.y, sz * v.x + cz * v.y, v.z]
+    }
+    pub fn point(&self, p: Vec3) -> Vec3 {
+        let s = self.scale;
+        self.rotate(vec3![p.x * s.x, p.y * s.y, p.z * s.z]) + self.translate
+    }
+    // Normals take the inverse scale so they stay perpendicular to scaled surfaces.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let s = self.scale;
+        self.rotate(vec3![n.x / s.x, n.y / s.y, n.z / s.z])
+            .normalize()
+    }
+}
+
+#[derive(Debug)]
+pub struct ObjError {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+// One triangle of a mesh, vertices in counter-clockwise order seen from the front as in the
+// file, also after a mirroring transform.
+pub struct Face {
+    pub vertices: [Vec3; 3],
+    pub normals: Option<[Vec3; 3]>,
+    pub uvs: Option<[Vec2; 3]>,
+    // name given by the last `usemtl`, if any
+    pub material: Option<String>,
+}
+
+// Errors don't name `path`, callers know which file they asked for.
+pub fn load(path: &Path, transform: &Transform) -> Result<Vec<Face>, ObjError> {
+    let source = fs::read_to_string(path).map_err(|err| ObjError {
+        line: None,
+        message: err.to_string(),
+    })?;
+    parse(&source, transform)
+}
+
+pub fn parse(source: &str, transform: &Transform) -> Result<Vec<Face>, ObjError> {
+    let mut positions: Vec<Vec3> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut uvs: Vec<Vec2> = vec![];
+    let mut material: Option<String> = None;
+    let mut faces = vec![];
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| ObjError {
+            line: Some(number + 1),
+            message,
+        };
+        let line = line.split('#').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = words.collect();
+        let floats = |min: usize| -> Result<Vec<f32>, ObjError> {
+            if args.len() < min {
+                return Err(error(format!(
+                    "`{}` needs at least {} values",
+                    keyword, min
+                )));
+            }
+            args.iter()
+                .map(|arg| {
+                    arg.parse()
+                        .map_err(|_| error(format!("invalid number `{}`", arg)))
+                })
+                .collect()
+        };
+
+        match keyword {
+            "v" => {
+                let v = floats(3)?;
+                positions.push(transform.point(vec3![v[0], v[1], v[2]]));
+            }
+            "vn" => {
+                let n = floats(3)?;
+                normals.push(transform.normal(vec3![n[0], n[1], n[2]]));
+            }
+            "vt" => {
+                let t = floats(1)?;
+                uvs.push(vec2![t[0], t.get(1).copied().unwrap_or(0.0)]);
+            }
+            "usemtl" => material = args.first().map(|name| name.to_string()),
+            "f" => {
+                if args.len() < 3 {
+                    return Err(error("a face needs at least 3 vertices".to_string()));
+                }
+                let corners = args
+                    .iter()
+                    .map(|arg| corner(arg, &positions, &uvs, &normals))
+                    .collect::<Result<Vec<_>, String>>()
+                    .map_err(error)?;
+                // fan triangulation, fine for the convex polygons exporters write; a mirroring
+                // transform turns the winding around, swapping two corners turns it back
+                for i in 1..corners.len() - 1 {
+                    let [a, mut b, mut c] = [&corners[0], &corners[i], &corners[i + 1]];
+                    if transform.mirrors() {
+                        std::mem::swap(&mut b, &mut c);
+                    }
+                    faces.push(Face {
+                        vertices: [a.0, b.0, c.0],
+                        uvs: a.1.zip(b.1).zip(c.1).map(|((a, b), c)| [a, b, c]),
+                        normals: a.2.zip(b.2).zip(c.2).map(|((a, b), c)| [a, b, c]),
+                        material: material.clone(),
+                    });
+                }
+            }
+            // groups, objects, smoothing groups, material libraries and free-form geometry
+            _ => {}
+        }
+    }
+    Ok(faces)
+}
+
+type Corner = (Vec3, Option<Vec2>, Option<Vec3>);
+
+// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, indices are 1-based or negative (relative to the end).
+fn corner(arg: &str, positions: &[Vec3], uvs: &[Vec2], normals: &[Vec3]) -> Result<Corner, String> {
+    let mut parts = arg.split('/');
+    let position = lookup(parts.next(), positions, "vertex")?
+        .ok_or_else(|| format!("face vertex `{}` has no position", arg))?;
+    let uv = lookup(parts.next(), uvs, "texture coordinate")?;
+    let normal = lookup(parts.next(), normals, "normal")?;
+    Ok((position, uv, normal))
+}
+
+fn lookup<T: Copy>(index: Option<&str>, items: &[T], what: &str) -> Result<Option<T>, String> {
+    let index = match index {
+        Some(index) if !index.is_empty() => index,
+        _ => return Ok(None),
+    };
+    let i: i64 = index
+        .parse()
+        .map_err(|_| format!("invalid {} index `{}`", what, index))?;
+    let resolved = if i < 0 { items.len() as i64 + i } else { i - 1 };
+    if i == 0 || resolved < 0 || resolved >= items.len() as i64 {
+        return Err(format!("{} index {} out of range", what, i));
+    }
+    Ok(Some(items[resolved as usize]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: Transform = Transform {
+        translate: Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        rotate: Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        scale: Vec3 {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        },
+    };
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\n";
+
+    fn faces(body: &str) -> Vec<Face> {
+        parse(&format!("{}{}", SQUARE, body), &IDENTITY).unwrap()
+    }
+
+    fn corners() -> [Vec3; 4] {
+        [
+            vec3![0.0, 0.0, 0.0],
+            vec3![1.0, 0.0, 0.0],
+            vec3![1.0, 1.0, 0.0],
+            vec3![0.0, 1.0, 0.0],
+        ]
+    }
+
+    #[test]
+    fn quads_become_fans() {
+        let [a, b, c, d] = corners();
+        let faces = faces("f 1 2 3 4\n");
+        assert_eq!(faces.len(), 2);
+        assert!(faces[0].vertices == [a, b, c]);
+        assert!(faces[1].vertices == [a, c, d]);
+    }
+
+    #[test]
+    fn negative_indices_count_back() {
+        let [_, b, c, d] = corners();
+        let faces = faces("f -3 -2 -1\nv 5 5 5\nf -4 -3 -2\n");
+        assert!(faces[0].vertices == [b, c, d]);
+        assert!(faces[1].vertices == [b, c, d]);
+    }
+
+    #[test]
+    fn corner_forms() {
+        let faces = faces("f 1 2 3\nf 1/1 2/2 3/3\nf 1//1 2//1 3//1\nf 1/1/1 2/2/1 3/3/1\n");
+        let uvs = [vec2![0.0, 0.0], vec2![1.0, 0.0], vec2![1.0, 1.0]];
+        let normals = [vec3![0.0, 0.0, 1.0]; 3];
+        let found: Vec<_> = faces
+            .iter()
+            .map(|face| (face.uvs.is_some(), face.normals.is_some()))
+            .collect();
+        assert_eq!(
+            found,
+            [(false, false), (true, false), (false, true), (true, true)]
+        );
+        assert!(faces[1].uvs == Some(uvs));
+        assert!(faces[2].normals == Some(normals));
+        assert!(faces[3].uvs == Some(uvs) && faces[3].normals == Some(normals));
+    }
+
+    #[test]
+    fn usemtl_switches_material() {
+        let faces = faces("f 1 2 3\nusemtl red\nf 1 2 3\nf 1 3 4\nusemtl blue\nf 1 2 3\n");
+        let names: Vec<Option<&str>> = faces.iter().map(|f| f.material.as_deref()).collect();
+        assert_eq!(names, [None, Some("red"), Some("red"), Some("blue")]);
+    }
+
+    #[test]
+    fn out_of_range_index() {
+        for (face, message) in [
+            ("f 1 2 5", "vertex index 5 out of range"),
+            ("f 0 1 2", "vertex index 0 out of range"),
+            ("f 1 2 -5", "vertex index -5 out of range"),
+            ("f 1/4 2/1 3/1", "texture coordinate index 4 out of range"),
+            ("f 1//2 2//1 3//1", "normal index 2 out of range"),
+        ] {
+            let err = parse(&format!("{}{}\n", SQUARE, face), &IDENTITY)
+                .err()
+                .unwrap_or_else(|| panic!("parsed {}", face));
+            assert_eq!(err.line, Some(9), "{}", err);
+            assert_eq!(err.message, message);
+        }
+    }
+
+    #[test]
+    fn mirroring_keeps_winding() {
+        let mirror = Transform {
+            scale: vec3![-1.0, 1.0, 1.0],
+            ..IDENTITY
+        };
+        let face = &parse(&format!("{}f 1 2 3\n", SQUARE), &mirror).unwrap()[0];
+        let [a, b, c] = face.vertices;
+        // counter-clockwise seen from +z, where the unmirrored square faces
+        assert!((b - a).cross(c - a).z > 0.0);
+    }
+}
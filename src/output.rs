@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::aov::Aov;
+use crate::color::Color;
+use crate::exr::{self, Layer, Precision};
+use crate::hdr;
+
+// Everything a render can write out.
+pub struct Images<'a> {
+    pub width: usize,
+    pub height: usize,
+    // the displayed frame
+    pub rgba: &'a [u8],
+    // unclamped linear radiance
+    pub screen: &'a [Color],
+    pub aovs: &'a [(Aov, Vec<Color>)],
+    pub precision: Precision,
+}
+
+// Writes the images, picking the format from the file extension. PNG and PPM take the
+// displayed frame, OpenEXR and Radiance HDR the linear screen. OpenEXR keeps the output
+// variables as layers of the same file, the other formats write them next to it as
+// `name.albedo.png` and so on.
+pub fn save(path: &Path, images: &Images) -> io::Result<()> {
+    let (width, height) = (images.width, images.height);
+    let format = path.extension().and_then(|ext| ext.to_str());
+    match format {
+        Some("png") => write_png(path, width, height, images.rgba)?,
+        Some("ppm") => write_ppm(path, width, height, images.rgba)?,
+        Some("exr") => {
+            let mut layers = vec![Layer {
+                name: "",
+                pixels: images.screen,
+                channels: exr::RGBA,
+                precision: images.precision,
+            }];
+            for (aov, buffer) in images.aovs {
+                layers.push(Layer {
+                    name: aov.name(),
+                    pixels: buffer,
+                    channels: aov.channels(),
+                    // half floats only count up to 2048 exactly
+                    precision: match aov {
+                        Aov::Object => Precision::Float,
+                        _ => images.precision,
+                    },
+                });
+            }
+            return exr::write(path, width, height, &layers);
+        }
+        Some("hdr") => hdr::write(path, width, height, images.screen)?,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported image format: {}", path.display()),
+            ))
+        }
+    }
+    for (aov, buffer) in images.aovs {
+        let path = aov_path(path, *aov);
+        match format {
+            Some("hdr") => hdr::write(&path, width, height, buffer)?,
+            Some("png") => write_png(&path, width, height, &aov.to_rgba8(buffer))?,
+            _ => write_ppm(&path, width, height, &aov.to_rgba8(buffer))?,
+        }
+    }
+    Ok(())
+}
+
+// `path` with the output variable's name before the extension.
+pub fn aov_path(path: &Path, aov: Aov) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, aov.name(), ext.to_string_lossy()),
+        None => format!("{}.{}", stem, aov.name()),
+    };
+    path.with_file_name(name)
+}
+
+pub fn write_png(path: &Path, width: usize, height: usize, rgba: &[u8]) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(rgba).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+// Binary (P6) PPM, alpha is dropped.
+pub fn write_ppm(path: &Path, width: usize, height: usize, rgba: &[u8]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "P6\n{} {}\n255\n", width, height)?;
+    for pix in rgba.chunks_exact(4) {
+        file.write_all(&pix[..3])?;
+    }
+    file.flush()
+}
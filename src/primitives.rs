@@ -1,27 +1,80 @@
+use crate::bsdf::{self, BsdfSample};
+use crate::bvh::Aabb;
+use crate::obj::Transform;
+use crate::rng::Pcg;
 use crate::utils::*;
-use euler::{vec3, Vec3};
+use euler::{vec2, vec3, Vec2, Vec3};
+use rand::prelude::*;
 
-pub trait Object3d {
-    fn intersects(&self, ray: &Ray) -> bool;
-    fn get_t(&self, ray: &Ray) -> f32;
-    fn get_mat(&self) -> Material;
-    fn get_norm(&self, pos: Vec3) -> Vec3;
-    fn get_next_ray(&self, ray: &Ray) -> Ray {
-        let t = self.get_t(ray);
-        let pos = ray.pos + (t - 0.001) * ray.dir;
-        let norm = self.get_norm(pos);
+use std::f32::consts::PI;
+use std::sync::Arc;
 
-        let metallicity = self.get_mat().metallicity;
+pub struct HitRecord {
+    pub t: f32,
+    pub pos: Vec3,
+    // always faces against the incoming ray, `front_face` tells whether it was flipped
+    pub norm: Vec3,
+    pub front_face: bool,
+    #[allow(dead_code)]
+    pub uv: Vec2,
+    pub mat: Material,
+    // index in the scene's object list, filled in by the BVH
+    pub object: usize,
+}
 
-        let reflection = ray.dir + 2.0 * ray.dir.dot(vec3![] - norm) * norm;
+impl HitRecord {
+    pub fn from(ray: &Ray, t: f32, outward_norm: Vec3, uv: Vec2, mat: Material) -> Self {
+        let front_face = ray.dir.dot(outward_norm) < 0.0;
+        Self {
+            t,
+            pos: ray.pos + t * ray.dir,
+            norm: if front_face {
+                outward_norm
+            } else {
+                vec3![] - outward_norm
+            },
+            front_face,
+            uv,
+            mat,
+            object: 0,
+        }
+    }
+    pub fn sample_bsdf(&self, ray: &Ray, rng: &mut Pcg) -> Option<BsdfSample> {
+        bsdf::sample(
+            &self.mat,
+            self.norm,
+            self.front_face,
+            vec3![] - ray.dir,
+            rng,
+        )
+    }
+}
 
-        let direction =
-            (metallicity * reflection + (1.0 - metallicity) * random_normal(norm)).normalize();
+// A direction towards a point on an emitter, with the solid angle pdf of choosing it.
+pub struct LightSample {
+    pub dir: Vec3,
+    pub dist: f32,
+    pub pdf: f32,
+}
 
-        Ray::from(pos, direction)
-    }
+pub trait Object3d {
+    // Closest intersection with `t_min < t < t_max`.
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
+    fn bounds(&self) -> Aabb;
+    fn get_mat(&self) -> Material;
+    // Samples a point on the surface as seen from `from` at the ray time `time`, used when the
+    // object is a light.
+    fn sample_light(&self, from: Vec3, time: f32, rng: &mut Pcg) -> Option<LightSample>;
+    // Solid angle pdf of `sample_light` choosing the point `ray` hits at distance `t`.
+    fn light_pdf(&self, ray: &Ray, t: f32) -> f32;
+}
+
+// Objects that can be placed by a transform, see `Moving`.
+pub trait Transformable {
+    fn transformed(&self, transform: &Transform) -> Self;
 }
 
+#[derive(Clone)]
 pub struct Sphere {
     pos: Vec3,
     rad: f32,
@@ -35,120 +88,502 @@ impl Sphere {
     }
 }
 
-impl Object3d for Sphere {
-    fn intersects(&self, ray: &Ray) -> bool {
-        let v: Vec3 = ray.pos - self.pos;
-        let b: f32 = 2.0 * v.dot(ray.dir);
-        let c: f32 = v.dot(v) - self.rad * self.rad;
-        let d: f32 = b * b - 4.0 * c;
-        if d < 0.0 {
-            return false;
-        }
-        let t0: f32 = (-b - d.sqrt()) / 2.0;
-        let t1: f32 = (-b + d.sqrt()) / 2.0;
-        if t0 > 0.0 || t1 > 0.0 {
-            return true;
+impl Transformable for Sphere {
+    // Moves the center, the radius takes the average scale since spheres stay round.
+    fn transformed(&self, transform: &Transform) -> Self {
+        let s = transform.scale;
+        Self {
+            pos: transform.point(self.pos),
+            rad: self.rad * (s.x.abs() + s.y.abs() + s.z.abs()) / 3.0,
+            mat: self.mat,
         }
-        false
     }
-    fn get_t(&self, ray: &Ray) -> f32 {
+}
+
+impl Object3d for Sphere {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
         let v: Vec3 = ray.pos - self.pos;
-        let b: f32 = 2.0 * v.dot(ray.dir);
+        let b: f32 = v.dot(ray.dir);
         let c: f32 = v.dot(v) - self.rad * self.rad;
-        let d: f32 = b * b - 4.0 * c;
+        let d: f32 = b * b - c;
         if d < 0.0 {
-            return -1.0;
+            return None;
         }
-        let t0: f32 = (-b - d.sqrt()) / 2.0;
-        let t1: f32 = (-b + d.sqrt()) / 2.0;
-        t0.min(t1)
+        let t0: f32 = -b - d.sqrt();
+        let t1: f32 = -b + d.sqrt();
+        let t = [t0, t1].into_iter().find(|&t| t_min < t && t < t_max)?;
+
+        let norm = (ray.pos + t * ray.dir - self.pos) / self.rad;
+        let uv = vec2![
+            0.5 + norm.y.atan2(norm.x) / (2.0 * PI),
+            norm.z.clamp(-1.0, 1.0).acos() / PI
+        ];
+        Some(HitRecord::from(ray, t, norm, uv, self.mat))
+    }
+    fn bounds(&self) -> Aabb {
+        let rad = vec3![self.rad, self.rad, self.rad];
+        Aabb::from(self.pos - rad, self.pos + rad)
     }
     fn get_mat(&self) -> Material {
         self.mat
     }
-    fn get_norm(&self, pos: Vec3) -> Vec3 {
-        (pos - self.pos).normalize()
-    }
-}
-
-use rand::prelude::*;
-use rand_distr::StandardNormal;
+    // Uniform over the cone of directions the sphere covers.
+    fn sample_light(&self, from: Vec3, _time: f32, rng: &mut Pcg) -> Option<LightSample> {
+        let to_center = self.pos - from;
+        let dist2 = to_center.dot(to_center);
+        if dist2 <= self.rad * self.rad {
+            return None;
+        }
+        let cos_max = (1.0 - self.rad * self.rad / dist2).max(0.0).sqrt();
+        let cos = 1.0 - rng.gen::<f32>() * (1.0 - cos_max);
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.gen::<f32>();
+        let frame = Frame::from(to_center.normalize());
+        let dir = frame.to_world(vec3![sin * phi.cos(), sin * phi.sin(), cos]);
 
-fn random_normal(normal: Vec3) -> Vec3 {
-    let mut rng = thread_rng();
-    let x: f32 = rng.sample(StandardNormal);
-    let y: f32 = rng.sample(StandardNormal);
-    let z: f32 = rng.sample(StandardNormal);
-    let random_point = vec3![x, y, z].normalize();
-    if normal.dot(random_point) < 0f32 {
-        vec3![] - random_point
-    } else {
-        random_point
+        let ray = Ray::from(from, dir);
+        let dist = self
+            .hit(&ray, 0.0, f32::INFINITY)
+            .map_or(dist2.sqrt() - self.rad, |hit| hit.t);
+        Some(LightSample {
+            dir,
+            dist,
+            pdf: 1.0 / (2.0 * PI * (1.0 - cos_max)),
+        })
+    }
+    fn light_pdf(&self, ray: &Ray, _t: f32) -> f32 {
+        let to_center = self.pos - ray.pos;
+        let dist2 = to_center.dot(to_center);
+        if dist2 <= self.rad * self.rad {
+            return 0.0;
+        }
+        let cos_max = (1.0 - self.rad * self.rad / dist2).max(0.0).sqrt();
+        1.0 / (2.0 * PI * (1.0 - cos_max))
     }
 }
 
+#[derive(Clone)]
 pub struct Trig {
     v0: Vec3,
     v1: Vec3,
     v2: Vec3,
     mat: Material,
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[Vec2; 3]>,
 }
 
 impl Trig {
     pub fn from(v0: Vec3, v1: Vec3, v2: Vec3, mat: Material) -> Self {
-        Self { v0, v1, v2, mat }
+        Self {
+            v0,
+            v1,
+            v2,
+            mat,
+            normals: None,
+            uvs: None,
+        }
+    }
+    // Per-vertex normals, interpolated across the face for smooth shading.
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+    pub fn with_uvs(mut self, uvs: [Vec2; 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+impl Transformable for Trig {
+    fn transformed(&self, transform: &Transform) -> Self {
+        let [v0, v1, v2] = [self.v0, self.v1, self.v2].map(|v| transform.point(v));
+        let normals = self.normals.map(|n| n.map(|n| transform.normal(n)));
+        if !transform.mirrors() {
+            return Self {
+                v0,
+                v1,
+                v2,
+                normals,
+                ..self.clone()
+            };
+        }
+        // keep the winding clockwise as seen from the front
+        Self {
+            v0,
+            v1: v2,
+            v2: v1,
+            normals: normals.map(|[n0, n1, n2]| [n0, n2, n1]),
+            uvs: self.uvs.map(|[t0, t1, t2]| [t0, t2, t1]),
+            mat: self.mat,
+        }
     }
 }
 
 impl Object3d for Trig {
-    fn intersects(&self, ray: &Ray) -> bool {
-        let norm: Vec3 = (self.v1 - self.v0).cross(self.v2 - self.v0);
-        let a = norm.x;
-        let b = norm.y;
-        let c = norm.z;
-        let d = -(a * self.v0.x + b * self.v0.y + c * self.v0.z);
-        if norm.dot(ray.dir) == 0.0 {
-            return false;
-        }
-        let t = -(d + a * ray.pos.x + b * ray.pos.y + c * ray.pos.z)
-            / (a * ray.dir.x + b * ray.dir.y + c * ray.dir.z);
-        if t < 0.0 {
-            return false;
-        }
-        let m = ray.pos + t * ray.dir;
-        let a = self.v0 - m;
-        let b = self.v1 - m;
-        let c = self.v2 - m;
+    // Watertight ray/triangle test (Woop, Benthin, Wald 2013): the ray is sheared onto +z so
+    // the edge functions are evaluated identically for triangles sharing an edge, and hits
+    // exactly on an edge go to exactly one of them.
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let d = ray.dir;
+        let kz = if d.x.abs() > d.y.abs() {
+            if d.x.abs() > d.z.abs() {
+                0
+            } else {
+                2
+            }
+        } else if d.y.abs() > d.z.abs() {
+            1
+        } else {
+            2
+        };
+        let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+        if axis(d, kz) < 0.0 {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+        let sz = 1.0 / axis(d, kz);
+        let (sx, sy) = (axis(d, kx) * sz, axis(d, ky) * sz);
+
+        let (a, b, c) = (self.v0 - ray.pos, self.v1 - ray.pos, self.v2 - ray.pos);
+        let shear = |p: Vec3| {
+            let pz = axis(p, kz);
+            (axis(p, kx) - sx * pz, axis(p, ky) - sy * pz, sz * pz)
+        };
+        let ((ax, ay, az), (bx, by, bz), (cx, cy, cz)) = (shear(a), shear(b), shear(c));
+
+        let mut u = cx * by - cy * bx;
+        let mut v = ax * cy - ay * cx;
+        let mut w = bx * ay - by * ax;
+        // redo edges that land exactly on zero in double precision to break ties consistently
+        if u == 0.0 || v == 0.0 || w == 0.0 {
+            let f = |x: f32| x as f64;
+            u = (f(cx) * f(by) - f(cy) * f(bx)) as f32;
+            v = (f(ax) * f(cy) - f(ay) * f(cx)) as f32;
+            w = (f(bx) * f(ay) - f(by) * f(ax)) as f32;
+        }
+        if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+            return None;
+        }
+        // an edge belongs to the triangle it runs up in, or right in when it's level; its
+        // neighbour runs it the other way, so only one of them takes rays right on it
+        let owns = |(px, py): (f32, f32), (qx, qy): (f32, f32)| qy > py || (qy == py && qx > px);
+        let (a2, b2, c2) = ((ax, ay), (bx, by), (cx, cy));
+        if (u == 0.0 && !owns(b2, c2)) || (v == 0.0 && !owns(c2, a2)) || (w == 0.0 && !owns(a2, b2))
+        {
+            return None;
+        }
+        // zero for rays in the triangle's plane and for degenerate triangles
+        let det = u + v + w;
+        if det == 0.0 {
+            return None;
+        }
+        let t = (u * az + v * bz + w * cz) / det;
+        if t <= t_min || t >= t_max || t.is_nan() {
+            return None;
+        }
+
+        // barycentric weights of v0, v1 and v2
+        let [b0, b1, b2] = [u / det, v / det, w / det];
         let base = (self.v2 - self.v0).cross(self.v1 - self.v0).normalize();
-        if (a.cross(b).normalize() + base).length() > 0.01 {
-            return false;
+        let norm = match self.normals {
+            Some([n0, n1, n2]) => (b0 * n0 + b1 * n1 + b2 * n2).normalize(),
+            None => base,
+        };
+        let uv = match self.uvs {
+            Some([t0, t1, t2]) => b0 * t0 + b1 * t1 + b2 * t2,
+            // without texture coordinates shading gets the barycentric coordinates
+            None => vec2![b1, b2],
+        };
+        Some(HitRecord::from(ray, t, norm, uv, self.mat))
+    }
+    fn bounds(&self) -> Aabb {
+        Aabb::from_points(&[self.v0, self.v1, self.v2])
+    }
+    fn get_mat(&self) -> Material {
+        self.mat
+    }
+    // Uniform over the triangle's area.
+    fn sample_light(&self, from: Vec3, _time: f32, rng: &mut Pcg) -> Option<LightSample> {
+        let su = rng.gen::<f32>().sqrt();
+        let (b0, b1) = (1.0 - su, rng.gen::<f32>() * su);
+        let point = b0 * self.v0 + b1 * self.v1 + (1.0 - b0 - b1) * self.v2;
+        let to_point = point - from;
+        let dist = to_point.length();
+        if dist == 0.0 {
+            return None;
         }
-        if (b.cross(c).normalize() + base).length() > 0.01 {
-            return false;
+        let dir = to_point / dist;
+        let pdf = self.light_pdf(&Ray::from(from, dir), dist);
+        if pdf == 0.0 {
+            return None;
         }
-        if (c.cross(a).normalize() + base).length() > 0.01 {
-            return false;
+        Some(LightSample { dir, dist, pdf })
+    }
+    fn light_pdf(&self, ray: &Ray, t: f32) -> f32 {
+        let cross = (self.v2 - self.v0).cross(self.v1 - self.v0);
+        let area = 0.5 * cross.length();
+        let cos = (cross.normalize().dot(ray.dir)).abs();
+        if area == 0.0 || cos == 0.0 {
+            return 0.0;
         }
-        true
+        t * t / (area * cos)
+    }
+}
+
+// Rays are checked against the bounds of every time they can have, sampled this often across
+// the shutter on top of the keyframes inside it.
+const MOTION_STEPS: usize = 32;
+
+// An object following keyframed transforms, placed anew at each ray's time for motion blur.
+pub struct Moving<T> {
+    object: T,
+    keyframes: Arc<[(f32, Transform)]>,
+    // scene time ray times are relative to
+    time: f32,
+    bounds: Aabb,
+}
+
+impl<T: Object3d + Transformable + Clone> Moving<T> {
+    // `keyframes` are sorted by time, `shutter` as on the camera.
+    pub fn from(
+        object: T,
+        keyframes: Arc<[(f32, Transform)]>,
+        time: f32,
+        shutter: (f32, f32),
+    ) -> Self {
+        let (open, close) = (time + shutter.0, time + shutter.1);
+        let mut times: Vec<f32> = (0..=MOTION_STEPS)
+            .map(|i| open + (close - open) * i as f32 / MOTION_STEPS as f32)
+            .collect();
+        times.extend(
+            keyframes
+                .iter()
+                .map(|&(t, _)| t)
+                .filter(|t| (open..=close).contains(t)),
+        );
+        let mut moving = Self {
+            object,
+            keyframes,
+            time,
+            bounds: Aabb::EMPTY,
+        };
+        let bounds = times.iter().fold(Aabb::EMPTY, |bounds, &t| {
+            bounds.union(moving.at(t - time).bounds())
+        });
+        // between two sampled times a rotating object sweeps an arc that can leave the boxes at
+        // both ends, but no point gets farther from the nearer end than half the step at the
+        // fastest speed anything moves
+        let step = (close - open) / MOTION_STEPS as f32;
+        let pad = 0.5 * step * moving.max_speed(open, close);
+        moving.bounds = Aabb::from(
+            bounds.min - vec3![pad, pad, pad],
+            bounds.max + vec3![pad, pad, pad],
+        );
+        moving
+    }
+    // Upper bound of how fast any point of the object moves between `open` and `close`: the
+    // rotation angles add up to at most their sum in angular speed, turning points as far from
+    // the origin as the scaled object reaches, on top of the scaling and translation.
+    fn max_speed(&self, open: f32, close: f32) -> f32 {
+        let local = self.object.bounds();
+        let farthest = |a: f32, b: f32| a.abs().max(b.abs());
+        let reach = vec3![
+            farthest(local.min.x, local.max.x),
+            farthest(local.min.y, local.max.y),
+            farthest(local.min.z, local.max.z)
+        ]
+        .length();
+        let largest = |v: Vec3| v.x.abs().max(v.y.abs()).max(v.z.abs());
+        self.keyframes
+            .windows(2)
+            .filter(|keys| keys[1].0 >= open && keys[0].0 <= close && keys[1].0 > keys[0].0)
+            .map(|keys| {
+                let ((t0, a), (t1, b)) = (&keys[0], &keys[1]);
+                let turn = b.rotate - a.rotate;
+                let turn = (turn.x.abs() + turn.y.abs() + turn.z.abs()).to_radians();
+                let scale = largest(a.scale).max(largest(b.scale));
+                let distance = turn * scale * reach
+                    + largest(b.scale - a.scale) * reach
+                    + (b.translate - a.translate).length();
+                distance / (t1 - t0)
+            })
+            .fold(0.0, f32::max)
     }
-    fn get_t(&self, ray: &Ray) -> f32 {
-        let norm: Vec3 = (self.v1 - self.v0).cross(self.v2 - self.v0);
-        let a = norm.x;
-        let b = norm.y;
-        let c = norm.z;
-        let d = -(a * self.v0.x + b * self.v0.y + c * self.v0.z);
-        if norm.dot(ray.dir) == 0.0 {
-            return -1.0;
+    fn at(&self, time: f32) -> T {
+        let transform = interpolate(&self.keyframes, self.time + time, |a, b, f| {
+            Transform::lerp(&a, &b, f)
+        });
+        match transform {
+            Some(transform) => self.object.transformed(&transform),
+            None => self.object.clone(),
         }
-        let t = -(d + a * ray.pos.x + b * ray.pos.y + c * ray.pos.z)
-            / (a * ray.dir.x + b * ray.dir.y + c * ray.dir.z);
-        t
+    }
+}
+
+impl<T: Object3d + Transformable + Clone> Object3d for Moving<T> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.at(ray.time).hit(ray, t_min, t_max)
+    }
+    fn bounds(&self) -> Aabb {
+        self.bounds
     }
     fn get_mat(&self) -> Material {
-        self.mat
+        self.object.get_mat()
+    }
+    fn sample_light(&self, from: Vec3, time: f32, rng: &mut Pcg) -> Option<LightSample> {
+        self.at(time).sample_light(from, time, rng)
     }
-    fn get_norm(&self, _pos: Vec3) -> Vec3 {
-        (self.v2 - self.v0).cross(self.v1 - self.v0).normalize()
+    fn light_pdf(&self, ray: &Ray, t: f32) -> f32 {
+        self.at(ray.time).light_pdf(ray, t)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn mat() -> Material {
+        Material::from(Color::WHITE, 0.0, 0.0, Color::BLACK)
+    }
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-4
+    }
+
+    #[test]
+    fn shared_edge_hits_one_triangle() {
+        let (a, b, c, d) = (
+            vec3![0.0, 0.0, 0.0],
+            vec3![1.0, 0.0, 0.0],
+            vec3![0.0, 1.0, 0.0],
+            vec3![1.0, 1.0, 0.0],
+        );
+        let trigs = [Trig::from(a, b, c, mat()), Trig::from(b, d, c, mat())];
+        // along the diagonal from both sides, and on the edges' end points
+        for pos in [
+            vec3![0.5, 0.5, 1.0],
+            vec3![0.25, 0.75, -1.0],
+            vec3![0.75, 0.25, 3.0],
+        ] {
+            for dir in [vec3![0.0, 0.0, -1.0], vec3![0.0, 0.0, 1.0]] {
+                let ray = Ray::from(pos, dir);
+                let hits = trigs
+                    .iter()
+                    .filter(|trig| trig.hit(&ray, 0.0, f32::INFINITY).is_some())
+                    .count();
+                let ahead = (pos.z > 0.0) == (dir.z < 0.0);
+                assert_eq!(hits, ahead as usize, "{:?} {:?}", pos, dir);
+            }
+        }
+    }
+
+    #[test]
+    fn ray_in_plane_misses() {
+        let trig = Trig::from(
+            vec3![0.0, 0.0, 0.0],
+            vec3![1.0, 0.0, 0.0],
+            vec3![0.0, 1.0, 0.0],
+            mat(),
+        );
+        for (pos, dir) in [
+            (vec3![-1.0, 0.25, 0.0], vec3![1.0, 0.0, 0.0]),
+            (vec3![0.25, -1.0, 0.0], vec3![0.0, 1.0, 0.0]),
+            (vec3![-1.0, -1.0, 0.0], vec3![1.0, 1.0, 0.0].normalize()),
+        ] {
+            assert!(trig.hit(&Ray::from(pos, dir), 0.0, f32::INFINITY).is_none());
+        }
+    }
+
+    #[test]
+    fn backface_flips_normal() {
+        // wound clockwise seen from below, so the front faces -z
+        let trig = Trig::from(
+            vec3![0.0, 0.0, 0.0],
+            vec3![1.0, 0.0, 0.0],
+            vec3![0.0, 1.0, 0.0],
+            mat(),
+        );
+        let down = Ray::from(vec3![0.2, 0.2, 1.0], vec3![0.0, 0.0, -1.0]);
+        let hit = trig.hit(&down, 0.0, f32::INFINITY).unwrap();
+        assert!(!hit.front_face);
+        assert!(close(hit.norm, vec3![0.0, 0.0, 1.0]));
+        assert!((hit.t - 1.0).abs() < 1e-6);
+
+        let up = Ray::from(vec3![0.2, 0.2, -1.0], vec3![0.0, 0.0, 1.0]);
+        let hit = trig.hit(&up, 0.0, f32::INFINITY).unwrap();
+        assert!(hit.front_face);
+        assert!(close(hit.norm, vec3![0.0, 0.0, -1.0]));
+    }
+
+    #[test]
+    fn degenerate_triangle_misses() {
+        let (a, b) = (vec3![0.0, 0.0, 0.0], vec3![1.0, 1.0, 0.0]);
+        let ray = Ray::from(vec3![0.5, 0.5, 1.0], vec3![0.0, 0.0, -1.0]);
+        for trig in [
+            Trig::from(a, b, 0.5 * (a + b), mat()),
+            Trig::from(a, a, b, mat()),
+            Trig::from(a, a, a, mat()),
+        ] {
+            assert!(trig.hit(&ray, 0.0, f32::INFINITY).is_none());
+        }
+    }
+
+    #[test]
+    fn barycentrics_at_vertices() {
+        let vertices = [
+            vec3![0.0, 0.0, 0.0],
+            vec3![2.0, 0.0, 0.0],
+            vec3![0.0, 2.0, 0.0],
+        ];
+        let uvs = [vec2![0.1, 0.2], vec2![0.9, 0.3], vec2![0.4, 0.8]];
+        let [v0, v1, v2] = vertices;
+        let plain = Trig::from(v0, v1, v2, mat());
+        let textured = plain.clone().with_uvs(uvs);
+        // barycentric coordinates of v0, v1 and v2 themselves
+        let expected = [vec2![0.0, 0.0], vec2![1.0, 0.0], vec2![0.0, 1.0]];
+
+        for i in 0..3 {
+            // just inside the corner, exactly on it only one triangle of a fan may take the hit
+            let weights = [0, 1, 2].map(|j| if i == j { 0.998 } else { 0.001 });
+            let point = weights[0] * v0 + weights[1] * v1 + weights[2] * v2;
+            let ray = Ray::from(point + vec3![0.0, 0.0, 1.0], vec3![0.0, 0.0, -1.0]);
+
+            let hit = plain.hit(&ray, 0.0, f32::INFINITY).unwrap();
+            assert!((hit.uv - expected[i]).length() < 1e-2, "{:?}", hit.uv);
+            assert!(close(hit.pos, point));
+
+            let hit = textured.hit(&ray, 0.0, f32::INFINITY).unwrap();
+            assert!((hit.uv - uvs[i]).length() < 1e-2, "{:?}", hit.uv);
+        }
+    }
+
+    #[test]
+    fn moving_bounds_cover_the_sweep() {
+        let bar = Trig::from(
+            vec3![1.0, 0.0, 0.0],
+            vec3![3.0, 0.0, 0.0],
+            vec3![3.0, 0.1, 0.0],
+            mat(),
+        );
+        let key = |time: f32, angle: f32| {
+            let transform = Transform {
+                translate: vec3![0.0, 0.0, 0.5 * time],
+                rotate: vec3![0.0, 0.0, angle],
+                scale: vec3![1.0, 1.0, 1.0],
+            };
+            (time, transform)
+        };
+        // a full turn and a bit between sampled times, the sampled poses barely differ
+        let turns = MOTION_STEPS as f32 * 1.01;
+        let keyframes: Arc<[(f32, Transform)]> =
+            Arc::from(vec![key(0.0, 0.0), key(1.0, 360.0 * turns)]);
+        let moving = Moving::from(bar, keyframes, 0.0, (0.0, 1.0));
+        let bounds = moving.bounds();
+        for i in 0..=10000 {
+            let at = moving.at(i as f32 / 10000.0).bounds();
+            for (inner, outer) in [(at.min, bounds.min), (bounds.max, at.max)] {
+                assert!(inner.x >= outer.x && inner.y >= outer.y && inner.z >= outer.z);
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use rand::{Error, RngCore};
+
+const MULTIPLIER: u64 = 6364136223846793005;
+
+// PCG32 (O'Neill 2014): small, fast and good enough for Monte Carlo. Every sample of every
+// pixel gets its own generator, so renders come out the same whatever thread runs them.
+pub struct Pcg {
+    state: u64,
+    inc: u64,
+}
+
+// SplitMix64's finalizer, spreads nearby keys over all bits.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+impl Pcg {
+    pub fn from(seed: u64, pixel: u64, sample: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: mix(seed) << 1 | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(mix(mix(seed ^ mix(pixel)) ^ sample));
+        rng.next_u32();
+        rng
+    }
+}
+
+impl RngCore for Pcg {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+    fn next_u64(&mut self) -> u64 {
+        (self.next_u32() as u64) << 32 | self.next_u32() as u64
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
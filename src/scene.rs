@@ -1,25 +1,31 @@
 use crate::color::Color;
+use crate::light::Light;
 use crate::primitives::*;
+use crate::sky::{Constant, Sky};
 use crate::utils::*;
 use euler::vec3;
 
 use std::f32::consts::FRAC_PI_3;
+use std::sync::Arc;
 
 // pub const SKY_COLOR: Color = Color::SKYBLUE;
 pub const SKY_COLOR: Color = Color::BLACK;
 // pub const SKY_COLOR: Color = Color::WHITE;
 
+pub struct Scene {
+    pub camera: Camera,
+    pub objects: Vec<Box<dyn Object3d + Sync>>,
+    pub lights: Vec<Box<dyn Light + Sync>>,
+    pub sky: Arc<dyn Sky + Send + Sync>,
+}
+
 fn construct_camera() -> Camera {
     let pos = vec3!(2.0, 2.0, 2.0) * 2.0;
-    let dir = vec3![] - pos.normalize() * 1.0;
-    let base0 = vec3![0.0, 0.0, 1.0];
-    let base1 = vec3![] - base0.cross(dir).normalize();
-    let base2 = dir.cross(base1).normalize();
-    Camera::from(pos, dir, base1, base2)
+    Camera::look_at(pos, vec3![], vec3![0.0, 0.0, 1.0])
 }
 
-fn construct_objects(_t: f32) -> Vec<Box<dyn Object3d + Sync>> {
-    let t = 1.0;
+fn construct_objects(t: f32) -> Vec<Box<dyn Object3d + Sync>> {
+    let t = 1.0 + t;
     let t1 = t;
     let t2 = t + FRAC_PI_3 * 2.0;
     let t3 = t - FRAC_PI_3 * 2.0;
@@ -124,16 +130,18 @@ fn construct_objects(_t: f32) -> Vec<Box<dyn Object3d + Sync>> {
     ]
 }
 
-pub fn construct_scene(t: f32) -> (Camera, Vec<Box<dyn Object3d + Sync>>) {
-    (construct_camera(), construct_objects(t))
+pub fn construct_scene(t: f32) -> Scene {
+    Scene {
+        camera: construct_camera(),
+        objects: construct_objects(t),
+        lights: vec![],
+        sky: Arc::new(Constant::from(SKY_COLOR)),
+    }
 }
 
 /*fn construct_camera() -> Camera {
     let pos = vec3!(2.4, 0.0, 12.0);
-    let dir = vec3![] - pos.normalize();
-    let base1 = vec3![0.0, 1.0, 0.0];
-    let base2 = dir.cross(base1).normalize();
-    Camera::from(pos, dir, base1, base2)
+    Camera::look_at(pos, vec3![], vec3![-1.0, 0.0, 0.0])
 }
 
 fn construct_objects() -> Vec<Box<dyn Object3d + Sync>> {
@@ -169,4 +177,3 @@ fn construct_objects() -> Vec<Box<dyn Object3d + Sync>> {
 pub fn construct_scene(_t: f32) -> (Camera, Vec<Box<dyn Object3d + Sync>>) {
     (construct_camera(), construct_objects())
 }*/
-
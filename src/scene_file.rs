@@ -0,0 +1,828 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use euler::{vec3, Vec3};
+use serde::{de, Deserialize, Deserializer};
+use toml::Spanned;
+
+use crate::color::Color;
+use crate::hdr;
+use crate::light::*;
+use crate::obj::{self, Transform};
+use crate::primitives::*;
+use crate::scene::Scene;
+use crate::sky::*;
+use crate::tracer::SceneSource;
+use crate::utils::*;
+
+/*
+Scene files are TOML:
+
+sky = "BLACK"                       # color name or [r, g, b(, a)], or one of the tables:
+
+[sky]                               # blend between three colors
+type = "gradient"
+zenith = "SKYBLUE"
+horizon = "WHITE"
+ground = "GRAY"                     # default: horizon
+intensity = 1.0                     # default: 1, for every type
+
+[sky]                               # daylight from the sun position
+type = "physical"
+sun = [1.0, 0.5, 0.8]               # direction towards the sun
+turbidity = 3.0                     # haze, 2 (clear) to 10, default: 3
+
+[sky]                               # equirectangular Radiance .hdr environment map
+type = "hdr"
+path = "sky.hdr"                    # relative to the scene file
+rotate = 0.0                        # degrees around +z, default: 0
+
+[camera]
+pos = [4.0, 4.0, 4.0]
+target = [0.0, 0.0, 0.0]            # default: origin
+up = [0.0, 0.0, 1.0]                # default: +z
+fov = 90.0                          # vertical field of view in degrees, default: 90
+aperture = 0.1                      # lens diameter for depth of field, default: 0 (pinhole)
+focus = 6.9                         # distance in focus, default: distance to target
+shutter = [0.0, 0.5]                # open and close times relative to the frame for motion
+                                    # blur of keyframed objects, default: [0, 0], no blur
+keyframes = [                       # animated pos and target, blended linearly over time
+    { time = 0.0, pos = [4.0, 4.0, 4.0], target = [0.0, 0.0, 0.0] },
+    { time = 2.0, pos = [-4.0, 4.0, 4.0] },
+]
+
+[materials.gold]
+color = "GOLD"                      # base color
+metallicity = 0.0                   # or `metallic`, default: 0
+roughness = 0.0                     # default: 0
+specular = 0.5                      # default: 0, matte
+clearcoat = 0.0                     # default: 0
+clearcoat_roughness = 0.03          # default: 0.03
+emitting = 0.7                      # default: 0
+emitting_color = "ORANGE"           # default: BLACK
+transparency = 0.0                  # share of light refracted like glass, default: 0
+ior = 1.5                           # index of refraction, default: 1.5
+
+[[sphere]]
+pos = [3.0, -3.0, 0.7]
+rad = 0.7
+material = "gold"
+
+[[triangle]]
+vertices = [[5.0, 5.0, -1.0], [5.0, -5.0, -1.0], [-5.0, -5.0, -1.0]]
+material = "gold"
+
+[[mesh]]
+path = "models/prism.obj"           # Wavefront OBJ, relative to the scene file
+material = "gold"                   # for faces whose `usemtl` is not mapped
+materials = { Glass = "gold" }      # `usemtl` name -> material, default: same name
+translate = [0.0, 0.0, 1.0]         # default: 0
+rotate = [0.0, 0.0, 45.0]           # degrees around x, y, z, default: 0
+scale = 2.0                         # number or [x, y, z], default: 1
+
+Spheres, triangles and meshes take `keyframes` as well, a transform blended linearly between
+them and applied about the origin on top of the object's placement; spheres stay round and
+scale by the average factor:
+
+keyframes = [
+    { time = 0.0 },
+    { time = 1.0, translate = [0.0, 0.0, 2.0], rotate = [0.0, 0.0, 90.0], scale = 1.0 },
+]
+
+[[light]]                           # lights are never seen directly, only what they light up
+type = "point"                      # "directional", "point", "spot" or "area"
+color = "WHITE"                     # default: WHITE
+intensity = 10.0                    # default: 1
+pos = [0.0, 0.0, 5.0]               # point, spot
+dir = [0.0, 0.0, -1.0]              # direction the light travels in, directional, spot
+angle = 30.0                        # spot cone half angle in degrees, default: 30
+inner = 25.0                        # spot half angle where the falloff starts, default: angle
+corner = [-1.0, -1.0, 5.0]          # area: parallelogram shining towards edge1 x edge2
+edge1 = [0.0, 2.0, 0.0]
+edge2 = [2.0, 0.0, 0.0]
+*/
+
+#[derive(Debug)]
+pub struct SceneError {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl SceneError {
+    fn at(source: &str, span: Option<Range<usize>>, message: impl Into<String>) -> Self {
+        Self {
+            line: span.map(|span| line_of(source, span.start)),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+#[derive(Clone)]
+struct ColorDesc(Color);
+
+impl<'de> Deserialize<'de> for ColorDesc {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Name(String),
+            Channels(Vec<f32>),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Name(name) => Color::from_name(&name)
+                .map(ColorDesc)
+                .ok_or_else(|| de::Error::custom(format!("unknown color `{}`", name))),
+            Raw::Channels(c) => match c[..] {
+                [r, g, b] => Ok(ColorDesc(Color([r, g, b, 1.0]))),
+                [r, g, b, a] => Ok(ColorDesc(Color([r, g, b, a]))),
+                _ => Err(de::Error::custom("color must have 3 or 4 channels")),
+            },
+        }
+    }
+}
+
+fn black() -> ColorDesc {
+    ColorDesc(Color::BLACK)
+}
+
+fn white() -> ColorDesc {
+    ColorDesc(Color::WHITE)
+}
+
+enum SkyDesc {
+    Color(ColorDesc),
+    Kind(SkyKind),
+}
+
+impl<'de> Deserialize<'de> for SkyDesc {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // decided by hand instead of untagged so mistakes in either form keep their messages
+        let value = toml::Value::deserialize(deserializer)?;
+        let desc = if value.is_table() {
+            SkyKind::deserialize(value).map(SkyDesc::Kind)
+        } else {
+            ColorDesc::deserialize(value).map(SkyDesc::Color)
+        };
+        desc.map_err(|err| de::Error::custom(err.message()))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum SkyKind {
+    Constant {
+        color: ColorDesc,
+        #[serde(default = "one")]
+        intensity: f32,
+    },
+    Gradient {
+        zenith: ColorDesc,
+        horizon: ColorDesc,
+        ground: Option<ColorDesc>,
+        #[serde(default = "one")]
+        intensity: f32,
+    },
+    Physical {
+        sun: [f32; 3],
+        #[serde(default = "turbidity")]
+        turbidity: f32,
+        #[serde(default = "one")]
+        intensity: f32,
+    },
+    Hdr {
+        path: String,
+        #[serde(default)]
+        rotate: f32,
+        #[serde(default = "one")]
+        intensity: f32,
+    },
+}
+
+fn turbidity() -> f32 {
+    3.0
+}
+
+impl SkyDesc {
+    // `dir` is where environment map paths are resolved from.
+    fn build(&self, dir: &Path) -> Result<Arc<dyn Sky + Send + Sync>, String> {
+        Ok(match self {
+            SkyDesc::Color(color) => Arc::new(Constant::from(color.0)),
+            SkyDesc::Kind(SkyKind::Constant { color, intensity }) => {
+                Arc::new(Constant::from(color.0 * *intensity))
+            }
+            SkyDesc::Kind(SkyKind::Gradient {
+                zenith,
+                horizon,
+                ground,
+                intensity,
+            }) => {
+                let ground = ground.as_ref().unwrap_or(horizon);
+                Arc::new(Gradient::from(
+                    zenith.0 * *intensity,
+                    horizon.0 * *intensity,
+                    ground.0 * *intensity,
+                ))
+            }
+            SkyDesc::Kind(SkyKind::Physical {
+                sun,
+                turbidity,
+                intensity,
+            }) => Arc::new(Physical::from(to_vec3(*sun), *turbidity, *intensity)),
+            SkyDesc::Kind(SkyKind::Hdr {
+                path,
+                rotate,
+                intensity,
+            }) => {
+                let path = dir.join(path);
+                let image =
+                    hdr::load(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+                Arc::new(EnvMap::from(image, *rotate, *intensity))
+            }
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    pos: [f32; 3],
+    #[serde(default)]
+    target: [f32; 3],
+    #[serde(default = "z_up")]
+    up: [f32; 3],
+    #[serde(default = "fov")]
+    fov: f32,
+    #[serde(default)]
+    aperture: f32,
+    focus: Option<f32>,
+    #[serde(default)]
+    shutter: [f32; 2],
+    #[serde(default)]
+    keyframes: Vec<CameraKeyDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraKeyDesc {
+    time: f32,
+    pos: [f32; 3],
+    #[serde(default)]
+    target: [f32; 3],
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDesc {
+    time: f32,
+    #[serde(default)]
+    translate: [f32; 3],
+    #[serde(default)]
+    rotate: [f32; 3],
+    #[serde(default)]
+    scale: ScaleDesc,
+}
+
+fn to_keyframes(descs: &[KeyframeDesc]) -> Arc<[(f32, Transform)]> {
+    let mut keys: Vec<_> = descs
+        .iter()
+        .map(|key| (key.time, transform(key.translate, key.rotate, &key.scale)))
+        .collect();
+    keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+    keys.into()
+}
+
+fn fov() -> f32 {
+    90.0
+}
+
+fn z_up() -> [f32; 3] {
+    [0.0, 0.0, 1.0]
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDesc {
+    #[serde(alias = "base_color")]
+    color: ColorDesc,
+    #[serde(default, alias = "metallic")]
+    metallicity: f32,
+    #[serde(default)]
+    roughness: f32,
+    #[serde(default)]
+    specular: f32,
+    #[serde(default)]
+    clearcoat: f32,
+    #[serde(default = "clearcoat_roughness")]
+    clearcoat_roughness: f32,
+    #[serde(default)]
+    emitting: f32,
+    #[serde(default = "black")]
+    emitting_color: ColorDesc,
+    #[serde(default)]
+    transparency: f32,
+    #[serde(default = "glass_ior")]
+    ior: f32,
+}
+
+fn glass_ior() -> f32 {
+    1.5
+}
+
+fn clearcoat_roughness() -> f32 {
+    0.03
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SphereDesc {
+    pos: [f32; 3],
+    rad: f32,
+    material: Spanned<String>,
+    #[serde(default)]
+    keyframes: Vec<KeyframeDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TrigDesc {
+    vertices: [[f32; 3]; 3],
+    material: Spanned<String>,
+    #[serde(default)]
+    keyframes: Vec<KeyframeDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleDesc {
+    Uniform(f32),
+    Axes([f32; 3]),
+}
+
+impl Default for ScaleDesc {
+    fn default() -> Self {
+        ScaleDesc::Uniform(1.0)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshDesc {
+    path: Spanned<String>,
+    material: Option<Spanned<String>>,
+    #[serde(default)]
+    materials: HashMap<String, Spanned<String>>,
+    #[serde(default)]
+    translate: [f32; 3],
+    #[serde(default)]
+    rotate: [f32; 3],
+    #[serde(default)]
+    scale: ScaleDesc,
+    #[serde(default)]
+    keyframes: Vec<KeyframeDesc>,
+}
+
+fn transform(translate: [f32; 3], rotate: [f32; 3], scale: &ScaleDesc) -> Transform {
+    Transform {
+        translate: to_vec3(translate),
+        rotate: to_vec3(rotate),
+        scale: match *scale {
+            ScaleDesc::Uniform(s) => vec3![s, s, s],
+            ScaleDesc::Axes(s) => to_vec3(s),
+        },
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum LightDesc {
+    Directional {
+        dir: [f32; 3],
+        #[serde(default = "white")]
+        color: ColorDesc,
+        #[serde(default = "one")]
+        intensity: f32,
+    },
+    Point {
+        pos: [f32; 3],
+        #[serde(default = "white")]
+        color: ColorDesc,
+        #[serde(default = "one")]
+        intensity: f32,
+    },
+    Spot {
+        pos: [f32; 3],
+        dir: [f32; 3],
+        #[serde(default = "spot_angle")]
+        angle: f32,
+        inner: Option<f32>,
+        #[serde(default = "white")]
+        color: ColorDesc,
+        #[serde(default = "one")]
+        intensity: f32,
+    },
+    Area {
+        corner: [f32; 3],
+        edge1: [f32; 3],
+        edge2: [f32; 3],
+        #[serde(default = "white")]
+        color: ColorDesc,
+        #[serde(default = "one")]
+        intensity: f32,
+    },
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+fn spot_angle() -> f32 {
+    30.0
+}
+
+impl LightDesc {
+    fn build(&self) -> Box<dyn Light + Sync> {
+        match self {
+            LightDesc::Directional {
+                dir,
+                color,
+                intensity,
+            } => Box::new(Directional::from(to_vec3(*dir), color.0, *intensity)),
+            LightDesc::Point {
+                pos,
+                color,
+                intensity,
+            } => Box::new(Point::from(to_vec3(*pos), color.0, *intensity)),
+            LightDesc::Spot {
+                pos,
+                dir,
+                angle,
+                inner,
+                color,
+                intensity,
+            } => Box::new(Spot::from(
+                to_vec3(*pos),
+                to_vec3(*dir),
+                inner.unwrap_or(*angle).to_radians(),
+                angle.to_radians(),
+                color.0,
+                *intensity,
+            )),
+            LightDesc::Area {
+                corner,
+                edge1,
+                edge2,
+                color,
+                intensity,
+            } => Box::new(Area::from(
+                to_vec3(*corner),
+                to_vec3(*edge1),
+                to_vec3(*edge2),
+                color.0,
+                *intensity,
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    sky: Option<Spanned<SkyDesc>>,
+    camera: CameraDesc,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDesc>,
+    #[serde(default)]
+    sphere: Vec<SphereDesc>,
+    #[serde(default)]
+    triangle: Vec<TrigDesc>,
+    #[serde(default)]
+    mesh: Vec<MeshDesc>,
+    #[serde(default)]
+    light: Vec<LightDesc>,
+}
+
+fn to_vec3(v: [f32; 3]) -> Vec3 {
+    vec3![v[0], v[1], v[2]]
+}
+
+// Objects moved by the same keyframes: one sphere or triangle, a whole mesh, or every static
+// object with no keyframes at all.
+#[derive(Clone)]
+struct Group {
+    spheres: Vec<Sphere>,
+    trigs: Vec<Trig>,
+    keyframes: Arc<[(f32, Transform)]>,
+}
+
+impl Group {
+    fn from(keyframes: &[KeyframeDesc]) -> Self {
+        Self {
+            spheres: vec![],
+            trigs: vec![],
+            keyframes: to_keyframes(keyframes),
+        }
+    }
+}
+
+// A parsed scene with every material reference resolved, ready to be built as often as needed.
+#[derive(Clone)]
+pub struct SceneFile {
+    camera: Camera,
+    // time, pos and target
+    camera_keys: Vec<(f32, (Vec3, Vec3))>,
+    up: Vec3,
+    aperture: f32,
+    focus: Option<f32>,
+    sky: Arc<dyn Sky + Send + Sync>,
+    groups: Vec<Group>,
+    lights: Vec<LightDesc>,
+}
+
+impl SceneFile {
+    pub fn load(path: &Path) -> Result<Self, SceneError> {
+        let source = fs::read_to_string(path).map_err(|err| SceneError {
+            line: None,
+            message: format!("{}: {}", path.display(), err),
+        })?;
+        Self::parse(&source, path.parent().unwrap_or(Path::new("")))
+    }
+
+    // `dir` is where mesh paths are resolved from.
+    pub fn parse(source: &str, dir: &Path) -> Result<Self, SceneError> {
+        let desc: SceneDesc = toml::from_str(source)
+            .map_err(|err| SceneError::at(source, err.span(), err.message()))?;
+
+        let materials: HashMap<&str, Material> = desc
+            .materials
+            .iter()
+            .map(|(name, mat)| {
+                let mat = Material::from(
+                    mat.color.0,
+                    mat.metallicity,
+                    mat.emitting,
+                    mat.emitting_color.0,
+                )
+                .with_roughness(mat.roughness)
+                .with_specular(mat.specular)
+                .with_clearcoat(mat.clearcoat, mat.clearcoat_roughness)
+                .with_glass(mat.transparency, mat.ior);
+                (name.as_str(), mat)
+            })
+            .collect();
+        let material = |name: &Spanned<String>| {
+            materials
+                .get(name.get_ref().as_str())
+                .copied()
+                .ok_or_else(|| {
+                    SceneError::at(
+                        source,
+                        Some(name.span()),
+                        format!("unknown material `{}`", name.get_ref()),
+                    )
+                })
+        };
+
+        let mut groups = vec![Group::from(&[])];
+        let mut group_of = |keyframes: &[KeyframeDesc]| {
+            if keyframes.is_empty() {
+                return 0;
+            }
+            groups.push(Group::from(keyframes));
+            groups.len() - 1
+        };
+        let mut spheres = vec![];
+        for s in &desc.sphere {
+            let sphere = Sphere::from(to_vec3(s.pos), s.rad, material(&s.material)?);
+            spheres.push((group_of(&s.keyframes), sphere));
+        }
+        let mut trigs = vec![];
+        for t in &desc.triangle {
+            let [v0, v1, v2] = t.vertices.map(to_vec3);
+            let trig = Trig::from(v0, v1, v2, material(&t.material)?);
+            trigs.push((group_of(&t.keyframes), trig));
+        }
+
+        for mesh in &desc.mesh {
+            let path: PathBuf = dir.join(mesh.path.get_ref());
+            let placement = transform(mesh.translate, mesh.rotate, &mesh.scale);
+            let faces = obj::load(&path, &placement).map_err(|err| {
+                SceneError::at(
+                    source,
+                    Some(mesh.path.span()),
+                    format!("{}: {}", path.display(), err),
+                )
+            })?;
+            let group = group_of(&mesh.keyframes);
+            let fallback = mesh.material.as_ref().map(&material).transpose()?;
+            let mut mapped: HashMap<&str, Material> = HashMap::new();
+            for (usemtl, name) in &mesh.materials {
+                mapped.insert(usemtl, material(name)?);
+            }
+            for face in faces {
+                let mat = match &face.material {
+                    Some(usemtl) => mapped
+                        .get(usemtl.as_str())
+                        .or_else(|| materials.get(usemtl.as_str()))
+                        .copied()
+                        .or(fallback),
+                    None => fallback,
+                };
+                let mat = mat.ok_or_else(|| {
+                    SceneError::at(
+                        source,
+                        Some(mesh.path.span()),
+                        format!(
+                            "{}: no material for `usemtl {}`",
+                            path.display(),
+                            face.material.as_deref().unwrap_or("")
+                        ),
+                    )
+                })?;
+                // OBJ faces wind counter-clockwise, `Trig` expects clockwise
+                let [v0, v1, v2] = face.vertices;
+                let mut trig = Trig::from(v0, v2, v1, mat);
+                if let Some([n0, n1, n2]) = face.normals {
+                    trig = trig.with_normals([n0, n2, n1]);
+                }
+                if let Some([t0, t1, t2]) = face.uvs {
+                    trig = trig.with_uvs([t0, t2, t1]);
+                }
+                trigs.push((group, trig));
+            }
+        }
+        for (group, sphere) in spheres {
+            groups[group].spheres.push(sphere);
+        }
+        for (group, trig) in trigs {
+            groups[group].trigs.push(trig);
+        }
+
+        let sky = match &desc.sky {
+            Some(sky) => sky
+                .get_ref()
+                .build(dir)
+                .map_err(|err| SceneError::at(source, Some(sky.span()), err))?,
+            None => Arc::new(Constant::from(Color::BLACK)),
+        };
+
+        let cam = &desc.camera;
+        let (pos, target) = (to_vec3(cam.pos), to_vec3(cam.target));
+        let focus = cam.focus.unwrap_or((target - pos).length());
+        let camera = Camera::look_at(pos, target, to_vec3(cam.up))
+            .with_fov(cam.fov)
+            .with_lens(cam.aperture, focus)
+            .with_shutter(cam.shutter[0], cam.shutter[1]);
+        let mut camera_keys: Vec<_> = cam
+            .keyframes
+            .iter()
+            .map(|key| (key.time, (to_vec3(key.pos), to_vec3(key.target))))
+            .collect();
+        camera_keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Ok(Self {
+            camera,
+            camera_keys,
+            up: to_vec3(cam.up),
+            aperture: cam.aperture,
+            focus: cam.focus,
+            sky,
+            groups,
+            lights: desc.light,
+        })
+    }
+
+    pub fn build(&self, t: f32) -> Scene {
+        let mut objects: Vec<Box<dyn Object3d + Sync>> = vec![];
+        let (open, close) = self.camera.shutter();
+        for group in &self.groups {
+            let transform = interpolate(&group.keyframes, t, |a, b, f| Transform::lerp(&a, &b, f));
+            match transform {
+                // blurred along the motion while the shutter is open
+                Some(_) if open < close => {
+                    let keyframes = &group.keyframes;
+                    for sphere in &group.spheres {
+                        let sphere = sphere.clone();
+                        let moving = Moving::from(sphere, keyframes.clone(), t, (open, close));
+                        objects.push(Box::new(moving));
+                    }
+                    for trig in &group.trigs {
+                        let trig = trig.clone();
+                        let moving = Moving::from(trig, keyframes.clone(), t, (open, close));
+                        objects.push(Box::new(moving));
+                    }
+                }
+                Some(transform) => {
+                    for sphere in &group.spheres {
+                        objects.push(Box::new(sphere.transformed(&transform)));
+                    }
+                    for trig in &group.trigs {
+                        objects.push(Box::new(trig.transformed(&transform)));
+                    }
+                }
+                None => {
+                    for sphere in &group.spheres {
+                        objects.push(Box::new(sphere.clone()));
+                    }
+                    for trig in &group.trigs {
+                        objects.push(Box::new(trig.clone()));
+                    }
+                }
+            }
+        }
+
+        let view = interpolate(&self.camera_keys, t, |(p0, t0), (p1, t1), f| {
+            (p0 + f * (p1 - p0), t0 + f * (t1 - t0))
+        });
+        let camera = match view {
+            Some((pos, target)) => {
+                let camera = self.camera.with_view(pos, target, self.up);
+                match self.focus {
+                    Some(focus) => camera.with_lens(self.aperture, focus),
+                    None => camera,
+                }
+            }
+            None => self.camera,
+        };
+        Scene {
+            camera,
+            objects,
+            lights: self.lights.iter().map(LightDesc::build).collect(),
+            sky: self.sky.clone(),
+        }
+    }
+
+    pub fn into_source(self) -> SceneSource {
+        Box::new(move |t| self.build(t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parses `body` after a camera and a material on lines 1 to 5, returning the error.
+    fn error(body: &str) -> SceneError {
+        let source = format!(
+            "[camera]\npos = [4.0, 0.0, 0.0]\n\n[materials.white]\ncolor = \"WHITE\"\n{}",
+            body
+        );
+        match SceneFile::parse(&source, Path::new("")) {
+            Ok(_) => panic!("parsed:\n{}", source),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn unknown_material() {
+        let err = error("\n[[sphere]]\npos = [0.0, 0.0, 0.0]\nrad = 1.0\nmaterial = \"chrome\"\n");
+        assert_eq!(err.line, Some(10), "{}", err);
+        assert!(err.message.contains("unknown material `chrome`"), "{}", err);
+    }
+
+    #[test]
+    fn wrong_type() {
+        let err =
+            error("\n[[sphere]]\npos = [0.0, 0.0, 0.0]\nrad = \"big\"\nmaterial = \"white\"\n");
+        assert_eq!(err.line, Some(9), "{}", err);
+    }
+
+    #[test]
+    fn missing_field() {
+        let err = error("\n[[sphere]]\npos = [0.0, 0.0, 0.0]\nmaterial = \"white\"\n");
+        assert_eq!(err.line, Some(7), "{}", err);
+        assert!(err.message.contains("rad"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_color() {
+        let err = error("\n[materials.red]\ncolor = \"REDDISH\"\n");
+        assert_eq!(err.line, Some(8), "{}", err);
+        assert!(err.message.contains("unknown color `REDDISH`"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_light_kind() {
+        let err = error("\n[[light]]\nkind = \"laser\"\n");
+        assert_eq!(err.line, Some(7), "{}", err);
+        assert!(err.to_string().starts_with("line 7: "), "{}", err);
+    }
+}
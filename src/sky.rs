@@ -0,0 +1,351 @@
+use euler::{vec3, Vec3};
+use rand::prelude::*;
+
+use std::f32::consts::PI;
+
+use crate::color::Color;
+use crate::hdr;
+use crate::rng::Pcg;
+use crate::utils::Frame;
+
+// Radiance arriving from infinitely far away along rays that miss every object. Up is +z.
+
+// A direction towards the sky with the solid angle pdf of choosing it.
+pub struct SkySample {
+    pub dir: Vec3,
+    pub pdf: f32,
+}
+
+pub trait Sky {
+    fn radiance(&self, dir: Vec3) -> Color;
+    // Importance sampling for skies with bright features, the tracer only samples directions
+    // the sky returns here and finds the rest by bouncing.
+    fn sample(&self, _rng: &mut Pcg) -> Option<SkySample> {
+        None
+    }
+    fn pdf(&self, _dir: Vec3) -> f32 {
+        0.0
+    }
+}
+
+pub struct Constant {
+    color: Color,
+}
+
+impl Constant {
+    pub fn from(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Sky for Constant {
+    fn radiance(&self, _dir: Vec3) -> Color {
+        self.color
+    }
+}
+
+fn mix(a: Color, b: Color, t: f32) -> Color {
+    a * (1.0 - t) + b * t
+}
+
+// Blends from `horizon` up to `zenith` above the horizon and down to `ground` below it.
+pub struct Gradient {
+    zenith: Color,
+    horizon: Color,
+    ground: Color,
+}
+
+impl Gradient {
+    pub fn from(zenith: Color, horizon: Color, ground: Color) -> Self {
+        Self {
+            zenith,
+            horizon,
+            ground,
+        }
+    }
+}
+
+impl Sky for Gradient {
+    fn radiance(&self, dir: Vec3) -> Color {
+        if dir.z >= 0.0 {
+            mix(self.horizon, self.zenith, dir.z)
+        } else {
+            mix(self.horizon, self.ground, -dir.z)
+        }
+    }
+}
+
+// Angular radius of the sun disk, radians.
+const SUN_RADIUS: f32 = 0.0047;
+// Scales the model's luminance in kcd/m^2 to about 1 for a clear midday sky.
+const SKY_SCALE: f32 = 0.1;
+// Sun disk radiance relative to the sky, gives an irradiance of about 3 on a facing surface.
+const SUN_RADIANCE: f32 = 4.0e4;
+
+// Preetham, Shirley, Smits 1999: "A Practical Analytic Model for Daylight". Clear sky
+// luminance and chromaticity from the Perez distribution, plus a sun disk dimmed and
+// reddened by the air it shines through. Below the horizon the horizon color continues.
+pub struct Physical {
+    sun: Vec3,
+    sun_color: Color,
+    // Perez coefficients for Y, x and y
+    perez: [[f32; 5]; 3],
+    // zenith Y, x and y divided by the Perez function at the zenith
+    zenith: [f32; 3],
+    intensity: f32,
+}
+
+fn perez([a, b, c, d, e]: [f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+    (1.0 + a * (b / cos_theta.max(1e-2)).exp())
+        * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+fn polynomial(t: f32, theta: f32, rows: [[f32; 4]; 3]) -> f32 {
+    let powers = [theta.powi(3), theta.powi(2), theta, 1.0];
+    let row = |r: [f32; 4]| r.iter().zip(powers).map(|(c, p)| c * p).sum::<f32>();
+    t * t * row(rows[0]) + t * row(rows[1]) + row(rows[2])
+}
+
+// CIE xyY to linear sRGB.
+fn from_xyy(x: f32, y: f32, lum: f32) -> Color {
+    if y <= 0.0 {
+        return Color([0.0, 0.0, 0.0, 1.0]);
+    }
+    let (cx, cz) = (x / y * lum, (1.0 - x - y) / y * lum);
+    let r = 3.2406 * cx - 1.5372 * lum - 0.4986 * cz;
+    let g = -0.9689 * cx + 1.8758 * lum + 0.0415 * cz;
+    let b = 0.0557 * cx - 0.2040 * lum + 1.0570 * cz;
+    Color([r.max(0.0), g.max(0.0), b.max(0.0), 1.0])
+}
+
+impl Physical {
+    // `sun` points towards the sun, `turbidity` goes from 2 (very clear) to about 10 (hazy).
+    pub fn from(sun: Vec3, turbidity: f32, intensity: f32) -> Self {
+        let sun = sun.normalize();
+        let t = turbidity;
+        // the model only holds for the sun above the horizon
+        let theta_s = sun.z.clamp(0.0, 1.0).acos().min(PI / 2.0 - 1e-3);
+        let coefficients = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_lum = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let zenith_x = polynomial(
+            t,
+            theta_s,
+            [
+                [0.00166, -0.00375, 0.00209, 0.0],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886],
+            ],
+        );
+        let zenith_y = polynomial(
+            t,
+            theta_s,
+            [
+                [0.00275, -0.00610, 0.00317, 0.0],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688],
+            ],
+        );
+        let zenith = [zenith_lum, zenith_x, zenith_y];
+        let zenith = [0, 1, 2].map(|i| zenith[i] / perez(coefficients[i], 1.0, theta_s));
+
+        // relative air mass (Kasten, Young 1989) and a rough per channel extinction, blue
+        // scatters most and haze dims everything
+        let elevation = 90.0 - theta_s.to_degrees();
+        let air_mass = 1.0 / (sun.z.max(0.0) + 0.50572 * (elevation + 6.07995).powf(-1.6364));
+        let haze = 0.02 * (t - 1.0);
+        let extinction = [0.03 + haze, 0.06 + haze, 0.14 + haze];
+        let [r, g, b] = extinction.map(|k| (-k * air_mass).exp());
+        Self {
+            sun,
+            sun_color: Color([r, g, b, 1.0]) * SUN_RADIANCE,
+            perez: coefficients,
+            zenith,
+            intensity,
+        }
+    }
+
+    fn sky(&self, dir: Vec3) -> Color {
+        let cos_theta = dir.z.max(0.0);
+        let gamma = dir.dot(self.sun).clamp(-1.0, 1.0).acos();
+        let [lum, x, y] =
+            [0, 1, 2].map(|i| self.zenith[i] * perez(self.perez[i], cos_theta, gamma));
+        from_xyy(x, y, lum * SKY_SCALE)
+    }
+
+    fn sun_pdf() -> f32 {
+        // 1 - cos written out to keep precision for the tiny cone
+        1.0 / (4.0 * PI * (SUN_RADIUS / 2.0).sin().powi(2))
+    }
+}
+
+impl Sky for Physical {
+    fn radiance(&self, dir: Vec3) -> Color {
+        let sky = self.sky(dir);
+        let sun = if dir.dot(self.sun) >= SUN_RADIUS.cos() {
+            self.sun_color
+        } else {
+            Color([0.0, 0.0, 0.0, 1.0])
+        };
+        (sky + sun) * self.intensity
+    }
+    // Only the sun disk is sampled, the rest of the sky is smooth enough for BSDF sampling.
+    fn sample(&self, rng: &mut Pcg) -> Option<SkySample> {
+        let cos = 1.0 - rng.gen::<f32>() * 2.0 * (SUN_RADIUS / 2.0).sin().powi(2);
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.gen::<f32>();
+        let dir = Frame::from(self.sun).to_world(vec3![sin * phi.cos(), sin * phi.sin(), cos]);
+        Some(SkySample {
+            dir: dir.normalize(),
+            pdf: Self::sun_pdf(),
+        })
+    }
+    fn pdf(&self, dir: Vec3) -> f32 {
+        if dir.dot(self.sun) >= SUN_RADIUS.cos() {
+            Self::sun_pdf()
+        } else {
+            0.0
+        }
+    }
+}
+
+// Equirectangular environment map: columns go around +z, starting at -x and turning
+// counter-clockwise by `rotate`, rows from the zenith down to the nadir. Pixels are importance
+// sampled by their luminance so small bright lights in the map are found directly.
+pub struct EnvMap {
+    image: hdr::Image,
+    rotate: f32,
+    intensity: f32,
+    // cumulative pixel weights, over the rows and within each row, normalized to end at 1
+    row_cdf: Vec<f32>,
+    col_cdf: Vec<f32>,
+    // sum of all weights
+    total: f32,
+}
+
+fn luminance([r, g, b]: [f32; 3]) -> f32 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+impl EnvMap {
+    // `rotate` turns the map around +z, degrees.
+    pub fn from(image: hdr::Image, rotate: f32, intensity: f32) -> Self {
+        let (w, h) = (image.width, image.height);
+        let mut col_cdf = Vec::with_capacity(h * (w + 1));
+        let mut row_cdf = vec![0.0];
+        let mut total = 0.0;
+        for row in 0..h {
+            let sin = (PI * (row as f32 + 0.5) / h as f32).sin();
+            let mut acc = 0.0;
+            col_cdf.push(0.0);
+            for col in 0..w {
+                acc += luminance(image.pixels[row * w + col]) * sin;
+                col_cdf.push(acc);
+            }
+            let start = col_cdf.len() - (w + 1);
+            for value in &mut col_cdf[start..] {
+                *value = if acc > 0.0 { *value / acc } else { 0.0 };
+            }
+            total += acc;
+            row_cdf.push(total);
+        }
+        for value in &mut row_cdf {
+            *value = if total > 0.0 { *value / total } else { 0.0 };
+        }
+        Self {
+            image,
+            rotate: rotate.to_radians(),
+            intensity,
+            row_cdf,
+            col_cdf,
+            total,
+        }
+    }
+
+    fn pixel_of(&self, dir: Vec3) -> (usize, usize) {
+        let (w, h) = (self.image.width, self.image.height);
+        let u = ((dir.y.atan2(dir.x) - self.rotate) / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        let v = dir.z.clamp(-1.0, 1.0).acos() / PI;
+        (
+            ((v * h as f32) as usize).min(h - 1),
+            ((u * w as f32) as usize).min(w - 1),
+        )
+    }
+
+    // Chance of `sample` picking the pixel.
+    fn probability(&self, row: usize, col: usize) -> f32 {
+        let cdf = &self.col_cdf[row * (self.image.width + 1)..];
+        (cdf[col + 1] - cdf[col]) * (self.row_cdf[row + 1] - self.row_cdf[row])
+    }
+}
+
+// Index of the bucket of a cumulative distribution `u` falls in.
+fn find(cdf: &[f32], u: f32) -> usize {
+    let i = cdf.partition_point(|&c| c <= u);
+    i.clamp(1, cdf.len() - 1) - 1
+}
+
+impl Sky for EnvMap {
+    fn radiance(&self, dir: Vec3) -> Color {
+        let (row, col) = self.pixel_of(dir);
+        let [r, g, b] = self.image.pixels[row * self.image.width + col];
+        Color([r, g, b, 1.0]) * self.intensity
+    }
+    fn sample(&self, rng: &mut Pcg) -> Option<SkySample> {
+        if self.total <= 0.0 {
+            return None;
+        }
+        let (w, h) = (self.image.width, self.image.height);
+        let row = find(&self.row_cdf, rng.gen());
+        let col = find(&self.col_cdf[row * (w + 1)..(row + 1) * (w + 1)], rng.gen());
+        let u = (col as f32 + rng.gen::<f32>()) / w as f32;
+        let v = (row as f32 + rng.gen::<f32>()) / h as f32;
+        let (phi, theta) = ((u - 0.5) * 2.0 * PI + self.rotate, v * PI);
+        let dir = vec3![
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos()
+        ];
+        let pdf = self.pdf(dir);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(SkySample { dir, pdf })
+    }
+    // Pixels are picked by weight and sampled uniformly in (u, v), which covers
+    // 2 pi^2 sin(theta) of solid angle per unit area.
+    fn pdf(&self, dir: Vec3) -> f32 {
+        if self.total <= 0.0 {
+            return 0.0;
+        }
+        let (row, col) = self.pixel_of(dir);
+        let sin = (1.0 - dir.z * dir.z).max(0.0).sqrt();
+        if sin <= 0.0 {
+            return 0.0;
+        }
+        let area = (self.image.width * self.image.height) as f32;
+        self.probability(row, col) * area / (2.0 * PI * PI * sin)
+    }
+}
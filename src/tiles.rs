@@ -0,0 +1,136 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// The order tiles are rendered in.
+#[derive(Clone, Copy)]
+pub enum Order {
+    // rows from the top, left to right
+    Scanline,
+    // outwards from the center, where the subject usually is
+    Spiral,
+    // along a Hilbert curve, neighbouring tiles follow each other
+    Hilbert,
+}
+
+impl FromStr for Order {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "scanline" => Ok(Self::Scanline),
+            "spiral" => Ok(Self::Spiral),
+            "hilbert" => Ok(Self::Hilbert),
+            _ => Err(format!("unknown tile order: {}", name)),
+        }
+    }
+}
+
+// A rectangle of pixels, clipped to the image.
+#[derive(Clone, Copy)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+// Cuts the image into tiles of `size` pixels square, in `order`.
+pub fn split(width: usize, height: usize, size: usize, order: Order) -> Vec<Tile> {
+    let size = size.max(1);
+    let (cols, rows) = (width.div_ceil(size), height.div_ceil(size));
+    let mut cells: Vec<(usize, usize)> = (0..rows)
+        .flat_map(|row| (0..cols).map(move |col| (col, row)))
+        .collect();
+    match order {
+        Order::Scanline => {}
+        Order::Spiral => {
+            // ring by ring around the center, clockwise within each ring
+            let center = (cols as f32 / 2.0 - 0.5, rows as f32 / 2.0 - 0.5);
+            let key = |&(col, row): &(usize, usize)| {
+                let (dx, dy) = (col as f32 - center.0, row as f32 - center.1);
+                let ring = dx.abs().max(dy.abs()).round() as i32;
+                (ring, dy.atan2(dx))
+            };
+            cells.sort_by(|a, b| {
+                let (a, b) = (key(a), key(b));
+                a.0.cmp(&b.0).then(a.1.total_cmp(&b.1))
+            });
+        }
+        Order::Hilbert => {
+            let side = cols.max(rows).next_power_of_two();
+            cells.sort_by_key(|&(col, row)| hilbert(side, col, row));
+        }
+    }
+    cells
+        .into_iter()
+        .map(|(col, row)| {
+            let (x, y) = (col * size, row * size);
+            Tile {
+                x,
+                y,
+                width: size.min(width - x),
+                height: size.min(height - y),
+            }
+        })
+        .collect()
+}
+
+// Distance along the Hilbert curve filling a `side` by `side` grid of the cell at `x`, `y`.
+fn hilbert(side: usize, mut x: usize, mut y: usize) -> usize {
+    let mut d = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as usize;
+        let ry = (y & s > 0) as usize;
+        d += s * s * ((3 * rx) ^ ry);
+        // rotate the quadrant so the curve continues where the last one ended
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+// How much of a render is done and an estimate of the time left, assuming the rest goes at
+// the pace so far.
+pub struct Progress {
+    pub done: usize,
+    pub total: usize,
+    pub start: Instant,
+}
+
+impl Progress {
+    pub fn fraction(&self) -> f32 {
+        match self.total {
+            0 => 1.0,
+            total => self.done as f32 / total as f32,
+        }
+    }
+    pub fn eta(&self) -> Option<Duration> {
+        if self.done == 0 {
+            return None;
+        }
+        let elapsed = self.start.elapsed().as_secs_f32();
+        let left = elapsed * (self.total - self.done.min(self.total)) as f32 / self.done as f32;
+        Some(Duration::from_secs_f32(left))
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:5.1}%", self.fraction() * 100.0)?;
+        match self.eta() {
+            Some(eta) => {
+                let secs = eta.as_secs();
+                write!(f, ", {}:{:02} left", secs / 60, secs % 60)
+            }
+            None => Ok(()),
+        }
+    }
+}
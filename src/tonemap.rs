@@ -0,0 +1,100 @@
+use std::str::FromStr;
+
+use crate::color::Color;
+
+// Curves squeezing unbounded radiance into the displayable range.
+#[derive(Clone, Copy)]
+pub enum ToneMap {
+    // cuts off everything above 1
+    Clamp,
+    // c / (1 + luminance), keeps hues but flattens highlights
+    Reinhard,
+    // Narkowicz's fit of the ACES filmic curve
+    Aces,
+    // Sobotka's AgX, desaturates bright colors towards white like film
+    Agx,
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "clamp" => Ok(Self::Clamp),
+            "reinhard" => Ok(Self::Reinhard),
+            "aces" => Ok(Self::Aces),
+            "agx" => Ok(Self::Agx),
+            _ => Err(format!("unknown tone map: {}", name)),
+        }
+    }
+}
+
+impl ToneMap {
+    fn apply(self, [r, g, b]: [f32; 3]) -> [f32; 3] {
+        match self {
+            Self::Clamp => [r, g, b],
+            Self::Reinhard => {
+                let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+                [r, g, b].map(|c| c / (1.0 + luminance))
+            }
+            Self::Aces => [r, g, b].map(|c| {
+                let c = c * 0.6;
+                c * (2.51 * c + 0.03) / (c * (2.43 * c + 0.59) + 0.14)
+            }),
+            Self::Agx => agx([r, g, b]),
+        }
+    }
+}
+
+fn mul(m: [[f32; 3]; 3], [r, g, b]: [f32; 3]) -> [f32; 3] {
+    m.map(|row| row[0] * r + row[1] * g + row[2] * b)
+}
+
+// Minimal AgX with a polynomial fit of the contrast curve (Wrensch 2023), back to linear.
+fn agx(rgb: [f32; 3]) -> [f32; 3] {
+    const INSET: [[f32; 3]; 3] = [
+        [0.842479, 0.078434, 0.079224],
+        [0.042328, 0.878469, 0.079166],
+        [0.042376, 0.078434, 0.879143],
+    ];
+    const OUTSET: [[f32; 3]; 3] = [
+        [1.196879, -0.098021, -0.099030],
+        [-0.052897, 1.151903, -0.098961],
+        [-0.052972, -0.098043, 1.151074],
+    ];
+    const MIN_EV: f32 = -12.47393;
+    const MAX_EV: f32 = 4.026069;
+
+    let encoded = mul(INSET, rgb).map(|c| {
+        let x = (c.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let (x2, x4) = (x * x, x * x * x * x);
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    });
+    mul(OUTSET, encoded).map(|c| c.max(0.0).powf(2.2))
+}
+
+// The post-process between accumulated radiance and the frame buffer: exposure, then a tone
+// map, leaving linear colors in [0, 1] for `Color::into_u8` to encode. Displayed frames are
+// opaque, alpha only goes to the float outputs.
+#[derive(Clone, Copy)]
+pub struct Display {
+    tone_map: ToneMap,
+    // in stops, each one doubles the brightness
+    exposure: f32,
+}
+
+impl Display {
+    pub fn new() -> Self {
+        Self::from(ToneMap::Aces, 0.0)
+    }
+    pub fn from(tone_map: ToneMap, exposure: f32) -> Self {
+        Self { tone_map, exposure }
+    }
+    pub fn apply(&self, color: Color) -> Color {
+        let [r, g, b, _] = color.0;
+        let scale = self.exposure.exp2();
+        let [r, g, b] = self.tone_map.apply([r * scale, g * scale, b * scale]);
+        Color([r, g, b, 1.0])
+    }
+}
@@ -2,113 +2,627 @@ use euler::vec3;
 use rand::prelude::*;
 use rayon::prelude::*;
 
+use crate::aov::{Aov, Features};
+use crate::bsdf;
+use crate::bvh::Bvh;
 use crate::color::*;
+use crate::denoise::{self, Guides};
+use crate::light::Light;
 use crate::primitives::*;
-use crate::scene::{construct_scene, SKY_COLOR};
+use crate::rng::Pcg;
+use crate::scene::{construct_scene, Scene};
+use crate::sky::{self, Sky};
+use crate::tiles::{self, Order, Progress, Tile};
+use crate::tonemap::Display;
 use crate::utils::*;
 
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Minimum distance along secondary rays, keeps them from hitting the surface they leave.
+const EPSILON: f32 = 1e-4;
+
+pub type SceneSource = Box<dyn Fn(f32) -> Scene + Sync>;
+
+pub const MIN_DEPTH: usize = 3;
+pub const MAX_DEPTH: usize = 64;
+pub const TILE_SIZE: usize = 32;
+// How long `draw` renders tiles before showing them.
+const FRAME_BUDGET: Duration = Duration::from_millis(16);
+
 pub struct Tracer {
-    side: usize,
+    width: usize,
+    height: usize,
+    source: SceneSource,
     camera: Camera,
+    // replaces the scene's camera once the viewer moves it
+    view: Option<Camera>,
     objects: Vec<Box<dyn Object3d + Sync>>,
+    bvh: Bvh,
+    lights: Vec<Box<dyn Light + Sync>>,
+    // indices of emitting objects, sampled directly at every bounce
+    emitters: Vec<usize>,
+    sky: Arc<dyn Sky + Send + Sync>,
+    // time the scene was last built for
+    time: f32,
     screen: Vec<Color>,
-    frames: f32,
+    // first-hit passes accumulated next to `screen`, see aov.rs; the requested ones, then what
+    // else the denoiser needs
+    aovs: Vec<(Aov, Vec<Color>)>,
+    exported: usize,
+    denoise: bool,
+    // what `draw` shows while denoising: the image filtered once the pass it was at completed
+    denoised: Option<(usize, Vec<Color>)>,
+    samples: usize,
+    // the image cut into tiles in the order they are rendered, and how many of them already
+    // have a sample of the pass in progress
+    tiles: Vec<Tile>,
+    next_tile: usize,
+    // when the samples so far were started
+    started: Instant,
+    // random numbers of every sample follow from this, the same seed renders the same image
+    seed: u64,
+    // set from anywhere to stop `render` between pixels
+    cancel: Arc<AtomicBool>,
+    // accumulation pauses after this many samples per pixel
+    target: Option<usize>,
+    // bounces every path survives, and the hard cap Russian roulette can't go past
+    min_depth: usize,
+    max_depth: usize,
+    // exposure and tone map from `screen` to displayed frames
+    display: Display,
 }
 
 impl Tracer {
-    pub fn from(side: usize) -> Self {
-        Self {
-            side,
+    pub fn from(width: usize, height: usize) -> Self {
+        Self::with_source(width, height, Box::new(construct_scene))
+    }
+    pub fn with_source(width: usize, height: usize, source: SceneSource) -> Self {
+        let mut tracer = Self {
+            width,
+            height,
+            source,
             camera: Camera::new(),
+            view: None,
             objects: vec![],
-            screen: vec![Color::BLACK; side * side],
-            frames: 0.0,
+            bvh: Bvh::new(),
+            lights: vec![],
+            emitters: vec![],
+            sky: Arc::new(sky::Constant::from(Color::BLACK)),
+            time: 0.0,
+            screen: vec![Color::BLACK; width * height],
+            aovs: vec![],
+            exported: 0,
+            denoise: false,
+            denoised: None,
+            samples: 0,
+            tiles: tiles::split(width, height, TILE_SIZE, Order::Spiral),
+            next_tile: 0,
+            started: Instant::now(),
+            seed: 0,
+            cancel: Arc::new(AtomicBool::new(false)),
+            target: None,
+            min_depth: MIN_DEPTH,
+            max_depth: MAX_DEPTH,
+            display: Display::new(),
+        };
+        tracer.set_scene(0.0);
+        tracer
+    }
+    pub fn with_depth(mut self, min_depth: usize, max_depth: usize) -> Self {
+        self.min_depth = min_depth.min(max_depth);
+        self.max_depth = max_depth;
+        self
+    }
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    pub fn with_display(mut self, display: Display) -> Self {
+        self.display = display;
+        self
+    }
+    pub fn with_aovs(mut self, aovs: &[Aov]) -> Self {
+        let size = self.width * self.height;
+        self.aovs = aovs
+            .iter()
+            .map(|&aov| (aov, vec![Color::BLACK; size]))
+            .collect();
+        self.exported = aovs.len();
+        self.set_denoise(self.denoise);
+        self
+    }
+    pub fn with_denoise(mut self, denoise: bool) -> Self {
+        self.set_denoise(denoise);
+        self
+    }
+    // Filters the image before it's shown or saved. Starts accumulating anew if the denoiser's
+    // guide buffers weren't there yet.
+    pub fn set_denoise(&mut self, denoise: bool) {
+        self.denoise = denoise;
+        self.denoised = None;
+        if !denoise {
+            return;
         }
+        let size = self.width * self.height;
+        let mut added = false;
+        for aov in denoise::GUIDES {
+            if !self.aovs.iter().any(|(other, _)| *other == aov) {
+                self.aovs.push((aov, vec![Color::BLACK; size]));
+                added = true;
+            }
+        }
+        if added {
+            self.reset();
+        }
+    }
+    pub fn denoise(&self) -> bool {
+        self.denoise
+    }
+    pub fn with_tiles(mut self, size: usize, order: Order) -> Self {
+        self.tiles = tiles::split(self.width, self.height, size, order);
+        self.next_tile = 0;
+        self
+    }
+    pub fn with_target(mut self, samples: usize) -> Self {
+        self.target = Some(samples);
+        self
+    }
+    fn aspect(&self) -> f32 {
+        self.width as f32 / self.height as f32
     }
     fn set_scene(&mut self, t: f32) {
-        let (camera, objects) = construct_scene(t);
-        self.camera = camera;
-        self.objects = objects;
+        let scene = (self.source)(t);
+        self.time = t;
+        self.camera = self.view.unwrap_or(scene.camera).with_aspect(self.aspect());
+        self.objects = scene.objects;
+        self.bvh = Bvh::from(&self.objects);
+        self.lights = scene.lights;
+        self.emitters = (0..self.objects.len())
+            .filter(|&i| self.objects[i].get_mat().emitting > 0.0)
+            .collect();
+        self.sky = scene.sky;
+    }
+    pub fn camera(&self) -> Camera {
+        self.camera
     }
+    // Looks through `camera` instead of the scene's and starts accumulating anew.
+    pub fn set_view(&mut self, camera: Camera) {
+        self.view = Some(camera);
+        self.camera = camera.with_aspect(self.aspect());
+        self.reset();
+    }
+    // Drops the accumulated samples.
+    pub fn reset(&mut self) {
+        self.screen.fill(Color::BLACK);
+        for (_, buffer) in &mut self.aovs {
+            buffer.fill(Color::BLACK);
+        }
+        self.samples = 0;
+        self.next_tile = 0;
+        self.denoised = None;
+        self.started = Instant::now();
+    }
+    // Scene time the image is of.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+    pub fn target(&self) -> Option<usize> {
+        self.target
+    }
+    // Progress towards the target sample count, counted in tiles.
+    pub fn progress(&self) -> Option<Progress> {
+        let target = self.target?;
+        Some(Progress {
+            done: self.samples * self.tiles.len() + self.next_tile,
+            total: target * self.tiles.len(),
+            start: self.started,
+        })
+    }
+    // Stops a `render` in progress when set, from any thread.
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        self.cancel.clone()
+    }
+    // Whether the target sample count is reached.
+    pub fn done(&self) -> bool {
+        self.target.is_some_and(|target| self.samples >= target)
+    }
+    pub fn screen(&self) -> &[Color] {
+        &self.screen
+    }
+    pub fn aovs(&self) -> &[(Aov, Vec<Color>)] {
+        &self.aovs[..self.exported]
+    }
+    fn aov(&self, aov: Aov) -> &[Color] {
+        self.aovs
+            .iter()
+            .find(|(other, _)| *other == aov)
+            .map_or(&[], |(_, buffer)| buffer)
+    }
+    // The accumulated radiance, denoised if asked to.
+    pub fn image(&self) -> Cow<'_, [Color]> {
+        if !self.denoise {
+            return Cow::Borrowed(&self.screen);
+        }
+        let guides = Guides {
+            albedo: self.aov(Aov::Albedo),
+            normal: self.aov(Aov::Normal),
+            depth: self.aov(Aov::Depth),
+            emission: self.aov(Aov::Emission),
+        };
+        Cow::Owned(denoise::denoise(
+            self.width,
+            self.height,
+            &self.screen,
+            &guides,
+        ))
+    }
+    // Renders tiles for a moment unless the target is reached and shows the result, finished
+    // tiles show up while the rest of the pass is still to come. The denoiser runs once per
+    // completed pass rather than every frame.
     pub fn draw(&mut self, t: f32, screen: &mut [u8]) {
-        self.set_scene(t);
-
-        let scr = self.side as f32 / 2.0;
-        self.screen = (0..self.side.pow(2))
+        self.set_time(t);
+        if !self.done() {
+            self.render_pass(Some(Instant::now() + FRAME_BUDGET));
+        }
+        let filtered = self.denoised.as_ref().map(|(samples, _)| *samples);
+        if self.denoise && self.samples > 0 && filtered != Some(self.samples) {
+            self.denoised = Some((self.samples, self.image().into_owned()));
+        }
+        self.write_frame(screen);
+    }
+    // Adds one sample per pixel of the scene at time `t`, the scene is only rebuilt and the
+    // samples so far dropped when the time changes.
+    pub fn accumulate(&mut self, t: f32) {
+        self.set_time(t);
+        self.render_pass(None);
+    }
+    // Renders `samples` samples per pixel of the scene at time `t` from scratch, one tile after
+    // the other, calling `on_tile` as each is done. Returns false if cancelled through
+    // `cancel_flag`, the tiles done so far stay.
+    pub fn render(
+        &mut self,
+        t: f32,
+        samples: usize,
+        mut on_tile: impl FnMut(&Tile, &Progress),
+    ) -> bool {
+        self.set_time(t);
+        self.reset();
+        // nothing to average, and no tiles to call back for
+        if samples == 0 {
+            return true;
+        }
+        self.cancel.store(false, Ordering::Relaxed);
+        let mut progress = Progress {
+            done: 0,
+            total: self.tiles.len(),
+            start: Instant::now(),
+        };
+        for tile in self.tiles.clone() {
+            if !self.render_tile(&tile, samples) {
+                return false;
+            }
+            progress.done += 1;
+            on_tile(&tile, &progress);
+        }
+        self.samples = samples;
+        true
+    }
+    fn set_time(&mut self, t: f32) {
+        if t != self.time {
+            self.set_scene(t);
+            self.reset();
+        }
+    }
+    // Adds a sample to the tiles of the current pass until it's complete or `until` has passed,
+    // returns whether it completed. A cancelled tile stops the pass where it is, like `render`.
+    fn render_pass(&mut self, until: Option<Instant>) -> bool {
+        while self.next_tile < self.tiles.len() {
+            if until.is_some_and(|until| Instant::now() >= until) {
+                return false;
+            }
+            let tile = self.tiles[self.next_tile];
+            if !self.render_tile(&tile, 1) {
+                return false;
+            }
+            self.next_tile += 1;
+        }
+        self.next_tile = 0;
+        self.samples += 1;
+        true
+    }
+    // Adds `samples` samples to every pixel of `tile` on top of the `self.samples` it has.
+    // A cancelled tile is left as it was and false returned.
+    fn render_tile(&mut self, tile: &Tile, samples: usize) -> bool {
+        let aovs: Vec<Aov> = self.aovs.iter().map(|(aov, _)| *aov).collect();
+        // per pixel the mean color and output variables of the new samples
+        let results: Option<Vec<(Color, Vec<Color>)>> = (0..tile.width * tile.height)
             .into_par_iter()
-            .map(|pos| {
-                let mut rng = thread_rng();
-                let (x, y) = (pos / self.side, pos % self.side);
-                let x_var: f32 = rng.gen::<f32>() - 0.5;
-                let y_var: f32 = rng.gen::<f32>() - 0.5;
-                let (x, y) = (x as f32 + x_var, y as f32 + y_var);
-                let (x, y) = ((y - scr) / scr, (x - scr) / scr);
-                (self.screen[pos] * self.frames + self.get_pixel_color(x, y)) / (self.frames + 1.0)
+            .map(|i| {
+                let (col, row) = (tile.x + i % tile.width, tile.y + i / tile.width);
+                let mut color = Color::BLACK;
+                let mut values = vec![Color::BLACK; aovs.len()];
+                for sample in 0..samples {
+                    if self.cancel.load(Ordering::Relaxed) {
+                        return None;
+                    }
+                    let pixel = (row * self.width + col) as u64;
+                    let mut rng = Pcg::from(self.seed, pixel, (self.samples + sample) as u64);
+                    let (c, features) = self.sample_pixel(col, row, &mut rng);
+                    color = color + c;
+                    for (value, aov) in values.iter_mut().zip(&aovs) {
+                        if aov.accumulates() || sample == 0 {
+                            *value = *value + features.get(*aov);
+                        }
+                    }
+                }
+                let k = samples as f32;
+                let values = values
+                    .iter()
+                    .zip(&aovs)
+                    .map(|(&value, aov)| if aov.accumulates() { value / k } else { value })
+                    .collect();
+                Some((color / k, values))
             })
             .collect();
+        let results = match results {
+            Some(results) => results,
+            None => return false,
+        };
 
-        self.frames += 1.0;
-
+        let (n, k) = (self.samples as f32, samples as f32);
+        let mix = |old: Color, new: Color| (old * n + new * k) / (n + k);
+        for (i, (color, values)) in results.iter().enumerate() {
+            let pos = (tile.y + i / tile.width) * self.width + tile.x + i % tile.width;
+            self.screen[pos] = mix(self.screen[pos], *color);
+            for ((aov, buffer), value) in self.aovs.iter_mut().zip(values) {
+                buffer[pos] = match aov.accumulates() {
+                    true => mix(buffer[pos], *value),
+                    false if n == 0.0 => *value,
+                    false => buffer[pos],
+                };
+            }
+        }
+        true
+    }
+    // One jittered sample of the pixel at `col`, `row`.
+    fn sample_pixel(&self, col: usize, row: usize, rng: &mut Pcg) -> (Color, Features) {
+        let (half_width, half_height) = (self.width as f32 / 2.0, self.height as f32 / 2.0);
+        let x = col as f32 + rng.gen::<f32>() - 0.5;
+        let y = row as f32 + rng.gen::<f32>() - 0.5;
+        self.get_pixel_color(
+            (x - half_width) / half_width,
+            (y - half_height) / half_height,
+            rng,
+        )
+    }
+    // The denoised image of the last completed pass, or the raw tiles until there is one.
+    fn write_frame(&self, screen: &mut [u8]) {
+        match &self.denoised {
+            Some((_, image)) if self.denoise => self.write_image(image, screen),
+            _ => self.write_image(&self.screen, screen),
+        }
+    }
+    // Tone maps `image`, as `image()` returns it, into `screen` as 8-bit RGBA.
+    pub fn write_image(&self, image: &[Color], screen: &mut [u8]) {
         for (pos, pix) in screen.chunks_exact_mut(4).enumerate() {
-            pix.copy_from_slice(&self.screen[pos].into_u8());
+            pix.copy_from_slice(&self.display.apply(image[pos]).into_u8());
         }
     }
 
-    pub fn get_pixel_color(&self, u: f32, v: f32) -> Color {
-        let ray: Ray = self.camera.get_ray(u, v);
-        const REFLECTION_LIMIT: usize = 5;
-        self.cast_ray(&ray, REFLECTION_LIMIT)
-    }
+    // Radiance along the camera ray through `u`, `v` and what it hits first.
+    pub fn get_pixel_color(&self, u: f32, v: f32, rng: &mut Pcg) -> (Color, Features) {
+        let mut ray: Ray = self.camera.get_ray(u, v, rng);
+        let mut radiance = Color::BLACK;
+        let mut throughput = Color::WHITE;
+        // pdf of the bounce that produced `ray`, `None` for camera rays and bounces light
+        // sampling can't reproduce
+        let mut bsdf_pdf: Option<f32> = None;
+        // alpha: whether the camera ray hits anything, sky pixels are transparent
+        let mut coverage = 0.0;
+        let mut features = Features::new();
 
-    fn cast_ray(&self, ray: &Ray, reflections: usize) -> Color {
-        let mut intersecting: Vec<&Box<dyn Object3d + Sync>> = Vec::new();
-        for object in &self.objects {
-            if object.intersects(ray) {
-                intersecting.push(object);
+        for depth in 0..=self.max_depth {
+            let hit = match self
+                .bvh
+                .closest(&self.objects, &ray, EPSILON, f32::INFINITY)
+            {
+                Some(hit) => {
+                    if depth == 0 {
+                        coverage = 1.0;
+                        features = Features {
+                            albedo: hit.mat.color,
+                            normal: hit.norm,
+                            depth: hit.t,
+                            object: Some(hit.object),
+                            emission: hit.mat.emitting_color * hit.mat.emitting,
+                        };
+                    }
+                    hit
+                }
+                None => {
+                    let mut sky = self.sky.radiance(ray.dir);
+                    if let Some(pdf) = bsdf_pdf {
+                        sky = sky * power_heuristic(pdf, self.sky.pdf(ray.dir));
+                    }
+                    radiance = radiance + throughput * sky;
+                    break;
+                }
+            };
+            let mut emmiting_color = hit.mat.emitting_color * hit.mat.emitting;
+            // the previous bounce already sampled this light directly, weight both strategies
+            if let Some(pdf) = bsdf_pdf {
+                if hit.mat.emitting > 0.0 {
+                    let light_pdf = self.objects[hit.object].light_pdf(&ray, hit.t)
+                        / self.emitters.len() as f32;
+                    emmiting_color = emmiting_color * power_heuristic(pdf, light_pdf);
+                }
+            }
+            let last = depth == self.max_depth;
+            let direct = self.sample_lights(&hit, &ray, rng)
+                + self.sample_emitters(&hit, &ray, last, rng)
+                + self.sample_sky(&hit, &ray, last, rng);
+            radiance = radiance + throughput * (direct + emmiting_color);
+            if last {
+                break;
             }
-        }
 
-        if intersecting.is_empty() {
-            return SKY_COLOR;
-        }
-
-        let to_collide = intersecting
-            .into_iter()
-            .map(|obj| (obj, obj.get_t(ray)))
-            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
-            .unwrap()
-            .0;
-
-        match reflections {
-            0 => {
-                let object_t = to_collide.get_t(ray);
-                let pos = ray.pos + ray.dir * (object_t - 0.001);
-                let norm = to_collide.get_norm(pos);
-                let light_direction = vec3![0.1, 0.1, -1.0];
-                let to_light = vec3![] - light_direction;
-                let light_ray = Ray::from(pos, to_light.normalize());
-                let ray_intersect: bool =
-                    self.objects.iter().any(|elem| elem.intersects(&light_ray));
-                let sky_color = if ray_intersect {
-                    Color::BLACK
-                } else {
-                    SKY_COLOR * to_collide.get_mat().color * norm.dot(to_light)
-                };
-                let emmiting_color =
-                    to_collide.get_mat().emitting_color * to_collide.get_mat().emitting;
+            let sample = match hit.sample_bsdf(&ray, rng) {
+                Some(sample) => sample,
+                None => break,
+            };
+            throughput = throughput * sample.weight;
+            bsdf_pdf = if sample.delta { None } else { Some(sample.pdf) };
+            ray = Ray::from(hit.pos, sample.dir).with_time(ray.time);
 
-                sky_color + emmiting_color
-            }
-            refl => {
-                let next_ray = to_collide.get_next_ray(ray);
-                let coming = self.cast_ray(&next_ray, refl - 1);
-                let coming_color = coming * to_collide.get_mat().color;
-                let emmiting_color =
-                    to_collide.get_mat().emitting_color * to_collide.get_mat().emitting;
-
-                coming_color + emmiting_color
+            // Russian roulette: end dim paths early, boosting the survivors to stay unbiased
+            if depth + 1 >= self.min_depth {
+                let [r, g, b, _] = throughput.0;
+                let survival = r.max(g).max(b).min(0.95);
+                if rng.gen::<f32>() >= survival {
+                    break;
+                }
+                throughput = throughput / survival;
             }
         }
+        let [r, g, b, _] = radiance.0;
+        (Color([r, g, b, coverage]), features)
+    }
+
+    // Light reaching the hit point straight from every light of the scene.
+    fn sample_lights(&self, hit: &HitRecord, ray: &Ray, rng: &mut Pcg) -> Color {
+        let wo = vec3![] - ray.dir;
+        self.lights
+            .iter()
+            .filter_map(|light| light.sample(hit.pos, rng))
+            .filter_map(|incident| {
+                let (value, _) = bsdf::eval(&hit.mat, hit.norm, wo, incident.dir);
+                let shadow_ray = Ray::from(hit.pos, incident.dir).with_time(ray.time);
+                let t_max = incident.dist * (1.0 - EPSILON);
+                if self.bvh.any(&self.objects, &shadow_ray, EPSILON, t_max) {
+                    return None;
+                }
+                Some(incident.radiance * value)
+            })
+            .fold(Color::BLACK, |acc, c| acc + c)
+    }
+
+    // Light arriving from a direction the sky picks, weighted against finding it by bouncing.
+    fn sample_sky(&self, hit: &HitRecord, ray: &Ray, last: bool, rng: &mut Pcg) -> Color {
+        let sample = match self.sky.sample(rng) {
+            Some(sample) => sample,
+            None => return Color::BLACK,
+        };
+        let (value, bsdf_pdf) = bsdf::eval(&hit.mat, hit.norm, vec3![] - ray.dir, sample.dir);
+        if bsdf_pdf == 0.0 {
+            return Color::BLACK;
+        }
+        let shadow_ray = Ray::from(hit.pos, sample.dir).with_time(ray.time);
+        if self
+            .bvh
+            .any(&self.objects, &shadow_ray, EPSILON, f32::INFINITY)
+        {
+            return Color::BLACK;
+        }
+        let weight = if last {
+            1.0
+        } else {
+            power_heuristic(sample.pdf, bsdf_pdf)
+        };
+        self.sky.radiance(sample.dir) * value * (weight / sample.pdf)
+    }
+
+    // Next event estimation: light arriving directly from a random point on a random emitter.
+    // On the last bounce nothing else can find the emitter, so light sampling takes all the weight.
+    fn sample_emitters(&self, hit: &HitRecord, ray: &Ray, last: bool, rng: &mut Pcg) -> Color {
+        if self.emitters.is_empty() {
+            return Color::BLACK;
+        }
+        let light = self.emitters[rng.gen_range(0..self.emitters.len())];
+        if light == hit.object {
+            return Color::BLACK;
+        }
+        let sample = match self.objects[light].sample_light(hit.pos, ray.time, rng) {
+            Some(sample) => sample,
+            None => return Color::BLACK,
+        };
+        let (value, bsdf_pdf) = bsdf::eval(&hit.mat, hit.norm, vec3![] - ray.dir, sample.dir);
+        if bsdf_pdf == 0.0 {
+            return Color::BLACK;
+        }
+        let shadow_ray = Ray::from(hit.pos, sample.dir).with_time(ray.time);
+        let t_max = sample.dist * (1.0 - EPSILON);
+        if self.bvh.any(&self.objects, &shadow_ray, EPSILON, t_max) {
+            return Color::BLACK;
+        }
+
+        let mat = self.objects[light].get_mat();
+        let light_pdf = sample.pdf / self.emitters.len() as f32;
+        let weight = if last {
+            1.0
+        } else {
+            power_heuristic(light_pdf, bsdf_pdf)
+        };
+        mat.emitting_color * mat.emitting * value * (weight / light_pdf)
+    }
+}
+
+// Multiple importance sampling weight of the strategy with `pdf` against the `other` one.
+fn power_heuristic(pdf: f32, other: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other * other);
+    if a + b == 0.0 {
+        return 0.0;
     }
+    a / (a + b)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // not a multiple of any tile size below, so there are partial tiles at the edges
+    const WIDTH: usize = 30;
+    const HEIGHT: usize = 22;
+    const SPP: usize = 4;
+
+    fn render(tile_size: usize, order: Order, seed: u64) -> Vec<u32> {
+        let mut tracer = Tracer::from(WIDTH, HEIGHT)
+            .with_tiles(tile_size, order)
+            .with_seed(seed);
+        assert!(tracer.render(0.0, SPP, |_, _| {}));
+        tracer
+            .screen()
+            .iter()
+            .flat_map(|pix| pix.0.map(f32::to_bits))
+            .collect()
+    }
+
+    #[test]
+    fn same_image_whatever_the_tiles() {
+        let image = render(TILE_SIZE, Order::Spiral, 0);
+        for (tile_size, order, name) in [
+            (TILE_SIZE, Order::Scanline, "scanline"),
+            (7, Order::Scanline, "scanline"),
+            (5, Order::Spiral, "spiral"),
+            (1, Order::Spiral, "spiral"),
+            (8, Order::Hilbert, "hilbert"),
+        ] {
+            assert!(
+                render(tile_size, order, 0) == image,
+                "{} tiles of {}",
+                name,
+                tile_size
+            );
+        }
+    }
+
+    #[test]
+    fn seed_changes_the_image() {
+        assert!(render(8, Order::Spiral, 0) != render(8, Order::Spiral, 1));
+    }
+}
@@ -1,55 +1,197 @@
 use crate::color::Color;
+use crate::rng::Pcg;
 use euler::{vec3, Vec3};
+use rand::prelude::*;
 
+use std::f32::consts::PI;
+
+// Pinhole camera by default, a thin lens once it has an aperture.
+#[derive(Clone, Copy)]
 pub struct Camera {
     pos: Vec3,
     dir: Vec3,
+    // unit vectors to the right of and down from `dir`
     base1: Vec3,
     base2: Vec3,
+    // image width over height, widens the view horizontally
+    aspect: f32,
+    // tangent of half the vertical field of view
+    tan_half_fov: f32,
+    // lens diameter, 0 keeps everything sharp
+    aperture: f32,
+    // distance along `dir` of the plane in focus
+    focus: f32,
+    // times the shutter opens and closes, relative to the scene time
+    shutter: (f32, f32),
 }
 
 impl Camera {
     pub fn new() -> Self {
-        Self {
-            pos: vec3![],
-            dir: vec3![1.0, 0.0, 0.0],
-            base1: vec3![0.0, 1.0, 0.0],
-            base2: vec3![0.0, 0.0, 1.0],
-        }
+        Self::look_at(vec3![], vec3![1.0, 0.0, 0.0], vec3![0.0, 0.0, 1.0])
     }
-    pub fn from(pos: Vec3, dir: Vec3, base1: Vec3, base2: Vec3) -> Self {
+    // Looks from `pos` towards `target` with `up` pointing up in the image, 90 degrees vertical
+    // field of view and focused on `target`.
+    pub fn look_at(pos: Vec3, target: Vec3, up: Vec3) -> Self {
+        let dir = (target - pos).normalize();
+        let base1 = vec3![] - up.cross(dir).normalize();
+        let base2 = dir.cross(base1).normalize();
         Self {
             pos,
             dir,
             base1,
             base2,
+            aspect: 1.0,
+            tan_half_fov: 1.0,
+            aperture: 0.0,
+            focus: (target - pos).length(),
+            shutter: (0.0, 0.0),
+        }
+    }
+    // Aims the camera anew, keeping its lens, shutter and field of view and focusing on `target`.
+    pub fn with_view(self, pos: Vec3, target: Vec3, up: Vec3) -> Self {
+        Self {
+            aspect: self.aspect,
+            tan_half_fov: self.tan_half_fov,
+            aperture: self.aperture,
+            shutter: self.shutter,
+            ..Self::look_at(pos, target, up)
+        }
+    }
+    pub fn pos(&self) -> Vec3 {
+        self.pos
+    }
+    // The point in focus straight ahead.
+    pub fn target(&self) -> Vec3 {
+        self.pos + self.focus * self.dir
+    }
+    // Vertical field of view in degrees.
+    pub fn with_fov(mut self, fov: f32) -> Self {
+        self.tan_half_fov = (fov.to_radians() / 2.0).tan();
+        self
+    }
+    // Depth of field: lens diameter and the distance that stays sharp.
+    pub fn with_lens(mut self, aperture: f32, focus: f32) -> Self {
+        self.aperture = aperture;
+        self.focus = focus;
+        self
+    }
+    // Motion blur: rays get times between `open` and `close`, relative to the scene time.
+    pub fn with_shutter(mut self, open: f32, close: f32) -> Self {
+        self.shutter = (open, close);
+        self
+    }
+    pub fn shutter(&self) -> (f32, f32) {
+        self.shutter
+    }
+    pub fn with_aspect(mut self, aspect: f32) -> Self {
+        self.aspect = aspect;
+        self
+    }
+    // `u` and `v` go from -1 to 1 across the image, left to right and top to bottom.
+    pub fn get_ray(&self, u: f32, v: f32, rng: &mut Pcg) -> Ray {
+        let offset = self.tan_half_fov * (u * self.aspect * self.base1 + v * self.base2);
+        let dir = self.dir + offset;
+        let (open, close) = self.shutter;
+        let time = match open < close {
+            true => rng.gen_range(open..close),
+            false => open,
+        };
+        if self.aperture <= 0.0 {
+            return Ray::from(self.pos, dir.normalize()).with_time(time);
         }
+
+        // start on a random point of the lens, aim at where the pinhole ray meets the focus plane
+        let r = 0.5 * self.aperture * rng.gen::<f32>().sqrt();
+        let phi = 2.0 * PI * rng.gen::<f32>();
+        let lens = r * phi.cos() * self.base1 + r * phi.sin() * self.base2;
+        let focus = self.pos + self.focus * dir;
+        let pos = self.pos + lens;
+        Ray::from(pos, (focus - pos).normalize()).with_time(time)
+    }
+}
+
+// The value at time `t`, blended between the keys around it and held before the first and
+// after the last. `keys` are sorted by time.
+pub fn interpolate<T: Copy>(keys: &[(f32, T)], t: f32, lerp: impl Fn(T, T, f32) -> T) -> Option<T> {
+    let i = keys.partition_point(|&(time, _)| time <= t);
+    match (i.checked_sub(1).map(|i| keys[i]), keys.get(i)) {
+        (Some((t0, a)), Some(&(t1, b))) => Some(lerp(a, b, (t - t0) / (t1 - t0))),
+        (Some((_, a)), None) | (None, Some(&(_, a))) => Some(a),
+        (None, None) => None,
     }
-    pub fn get_ray(&self, u: f32, v: f32) -> Ray {
-        Ray {
-            pos: self.pos,
-            dir: (self.dir + u * self.base1 + v * self.base2).normalize(),
+}
+
+pub fn axis(v: Vec3, axis: usize) -> f32 {
+    match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+// Orthonormal basis around a normal (Duff et al. 2017).
+pub struct Frame {
+    pub t: Vec3,
+    pub b: Vec3,
+    pub n: Vec3,
+}
+
+impl Frame {
+    pub fn from(n: Vec3) -> Self {
+        let sign = 1f32.copysign(n.z);
+        let a = -1.0 / (sign + n.z);
+        let b = n.x * n.y * a;
+        Self {
+            t: vec3![1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x],
+            b: vec3![b, sign + n.y * n.y * a, -n.y],
+            n,
         }
     }
+    pub fn to_local(&self, v: Vec3) -> Vec3 {
+        vec3![v.dot(self.t), v.dot(self.b), v.dot(self.n)]
+    }
+    pub fn to_world(&self, v: Vec3) -> Vec3 {
+        v.x * self.t + v.y * self.b + v.z * self.n
+    }
 }
 
 pub struct Ray {
     pub pos: Vec3,
     pub dir: Vec3,
+    // within the camera's shutter, relative to the scene time
+    pub time: f32,
 }
 
 impl Ray {
     pub fn from(pos: Vec3, dir: Vec3) -> Self {
-        Self { pos, dir }
+        Self {
+            pos,
+            dir,
+            time: 0.0,
+        }
+    }
+    pub fn with_time(mut self, time: f32) -> Self {
+        self.time = time;
+        self
     }
 }
 
+// Base color and metallicity as in the principled model most renderers share, see bsdf.rs.
 #[derive(Clone, Copy)]
 pub struct Material {
     pub color: Color,
     pub metallicity: f32,
+    // 0 is a mirror, 1 is fully rough
+    pub roughness: f32,
+    // reflectance of the non-metallic part, 0.5 is 4% at normal incidence, 0 is matte
+    pub specular: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
     pub emitting: f32,
     pub emitting_color: Color,
+    // share of light passing through the surface as through glass, see `with_glass`
+    pub transparency: f32,
+    pub ior: f32,
 }
 
 impl Material {
@@ -57,9 +199,33 @@ impl Material {
         Self {
             color,
             metallicity,
+            roughness: 0.0,
+            specular: 0.0,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
             emitting,
             emitting_color,
+            transparency: 0.0,
+            ior: 1.5,
         }
     }
+    pub fn with_roughness(mut self, roughness: f32) -> Self {
+        self.roughness = roughness;
+        self
+    }
+    pub fn with_specular(mut self, specular: f32) -> Self {
+        self.specular = specular;
+        self
+    }
+    pub fn with_clearcoat(mut self, clearcoat: f32, roughness: f32) -> Self {
+        self.clearcoat = clearcoat;
+        self.clearcoat_roughness = roughness;
+        self
+    }
+    // Dielectric with index of refraction `ior`, e.g. 1.33 for water or 1.5 for glass.
+    pub fn with_glass(mut self, transparency: f32, ior: f32) -> Self {
+        self.transparency = transparency;
+        self.ior = ior;
+        self
+    }
 }
-